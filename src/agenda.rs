@@ -0,0 +1,191 @@
+use chrono::naive::date::NaiveDate;
+
+use serde_json;
+
+use std::io::{self, Write};
+
+use ::database::Database;
+use ::event::Event;
+use ::locale;
+use ::one_day;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Everything written on one day, in the shape it's printed as JSON.
+#[derive(Serialize)]
+struct AgendaDay {
+    date: String,
+    note: Option<String>,
+    hours: Vec<AgendaHour>,
+    events: Vec<AgendaEvent>,
+}
+
+#[derive(Serialize)]
+struct AgendaHour {
+    hour: u8,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct AgendaEvent {
+    uid: String,
+    title: String,
+    start: String,
+    end: String,
+    all_day: bool,
+    location: Option<String>,
+    description: Option<String>,
+}
+
+impl AgendaEvent {
+    fn new(event: &Event) -> AgendaEvent {
+        AgendaEvent {
+            uid: event.uid.clone(),
+            title: event.title.clone(),
+            start: event.start.format(DATETIME_FORMAT).to_string(),
+            end: event.end.format(DATETIME_FORMAT).to_string(),
+            all_day: event.all_day,
+            location: event.location.clone(),
+            description: event.description.clone(),
+        }
+    }
+}
+
+/// Prints the entries and events between `from` and `to` inclusive, skipping empty days.
+pub fn print<W: Write>(db: &Database, from: NaiveDate, to: NaiveDate, json: bool, out: &mut W) -> Result<(), io::Error> {
+    db.prefetch(from, to);
+    let mut days = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let note = db.get_day(date);
+        let hours = (0..24)
+            .map(|hour| AgendaHour { hour, text: db.get_hour(date, hour) })
+            .filter(|entry| !entry.text.trim().is_empty())
+            .collect::<Vec<_>>();
+        let events = db.get_events(date);
+        if !note.trim().is_empty() || !hours.is_empty() || !events.is_empty() {
+            days.push((date, note, hours, events));
+        }
+        date = date + one_day();
+    }
+
+    if json {
+        let days = days.into_iter().map(|(date, note, hours, events)| AgendaDay {
+            date: date.format("%Y-%m-%d").to_string(),
+            note: match note.trim().is_empty() {
+                true => None,
+                false => Some(note),
+            },
+            hours,
+            events: events.iter().map(AgendaEvent::new).collect(),
+        }).collect::<Vec<_>>();
+        serde_json::to_writer_pretty(&mut *out, &days).map_err(io::Error::other)?;
+        return writeln!(out);
+    }
+
+    for (i, (date, note, hours, events)) in days.into_iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{}", locale::get().date_title(date))?;
+        for event in events.iter().filter(|event| event.all_day) {
+            writeln!(out, "    {}", event.title)?;
+        }
+        for line in note.lines().filter(|line| !line.trim().is_empty()) {
+            writeln!(out, "    {}", line)?;
+        }
+
+        // Hour entries and timed events are interleaved in order of their start times.
+        let mut lines = Vec::new();
+        for entry in hours {
+            let mut text = entry.text.lines().filter(|line| !line.trim().is_empty());
+            let first = text.next().unwrap_or("");
+            let mut entry_lines = vec![format!("{:02}:00       {}", entry.hour, first)];
+            entry_lines.extend(text.map(|line| format!("            {}", line)));
+            lines.push((date.and_hms(entry.hour as u32, 0, 0), entry_lines));
+        }
+        for event in events.iter().filter(|event| !event.all_day) {
+            let mut line = format!("{} {}", event.time_range(), event.title);
+            if let Some(ref location) = event.location {
+                line.push_str(&format!(" @ {}", location));
+            }
+            lines.push((event.start.max(date.and_hms(0, 0, 0)), vec![line]));
+        }
+        lines.sort_by_key(|&(start, _)| start);
+        for (_, entry_lines) in lines {
+            for line in entry_lines {
+                writeln!(out, "    {}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use ::database::Slot;
+    use ::editor::Editor;
+    use ::storage::memory::MemoryStorage;
+
+    fn database() -> Database {
+        let db = Database::new(Box::new(MemoryStorage::new()), Editor::new(None));
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Day(day), "Pay rent\n").unwrap();
+        db.set(Slot::Hour(day, 9), "Standup\nroom 2\n").unwrap();
+        db.set(Slot::Hour(day, 15), "Meeting\n").unwrap();
+        let mut event = Event::new(day, 10);
+        event.uid = String::from("dentist");
+        event.title = String::from("Dentist");
+        event.start = day.and_hms(10, 30, 0);
+        event.end = day.and_hms(11, 15, 0);
+        event.location = Some(String::from("High Street"));
+        db.put_event(&event).unwrap();
+        db.set(Slot::Hour(day + one_day() * 2, 8), "Breakfast").unwrap();
+        db
+    }
+
+    fn print_agenda(json: bool) -> String {
+        let from = NaiveDate::from_ymd(2017, 5, 31);
+        let mut out = Vec::new();
+        print(&database(), from, from + one_day() * 2, json, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text() {
+        let locale = locale::get();
+        assert_eq!(print_agenda(false), format!("\
+{}
+    Pay rent
+    09:00       Standup
+                room 2
+    10:30-11:15 Dentist @ High Street
+    15:00       Meeting
+
+{}
+    08:00       Breakfast
+", locale.date_title(NaiveDate::from_ymd(2017, 5, 31)), locale.date_title(NaiveDate::from_ymd(2017, 6, 2))));
+    }
+
+    #[test]
+    fn json() {
+        let days: Value = serde_json::from_str(&print_agenda(true)).unwrap();
+        let days = days.as_array().unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0]["date"], "2017-05-31");
+        assert_eq!(days[0]["note"], "Pay rent\n");
+        assert_eq!(days[0]["hours"][0]["hour"], 9);
+        assert_eq!(days[0]["hours"][0]["text"], "Standup\nroom 2\n");
+        assert_eq!(days[0]["events"][0]["uid"], "dentist");
+        assert_eq!(days[0]["events"][0]["start"], "2017-05-31T10:30");
+        assert_eq!(days[0]["events"][0]["end"], "2017-05-31T11:15");
+        assert_eq!(days[0]["events"][0]["all_day"], false);
+        assert_eq!(days[0]["events"][0]["location"], "High Street");
+        assert_eq!(days[0]["events"][0]["description"], Value::Null);
+        assert_eq!(days[1]["date"], "2017-06-02");
+        assert_eq!(days[1]["note"], Value::Null);
+    }
+}
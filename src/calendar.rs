@@ -1,53 +1,365 @@
 use tui::buffer::Buffer;
 use tui::layout::Rect;
 use tui::widgets::Widget;
-use xdg;
 
 use chrono::naive::datetime::NaiveDateTime;
+use chrono::offset::local::Local;
+use chrono::naive::date::NaiveDate;
 use chrono::Timelike;
 
+use termion::event::{Key, MouseButton, MouseEvent};
+
+use notify::RecommendedWatcher;
+
+use std::env;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use self::CalendarView::*;
+use ::year::YearView;
 use ::month::MonthView;
 use ::week::WeekView;
-use ::database::Database;
+use ::day::DayView;
+use ::{hour_name, one_day};
+use ::database::{Database, Slot};
+use ::text_editor::{EditResult, TextEditor};
+use ::prompt::{Prompt, PromptResult};
+use ::search::{SearchAction, SearchPane};
+use ::help::HelpPane;
+use ::keys::{self, Action, Keymap, Lookup};
+use ::status::StatusBar;
+use ::theme::Theme;
+use ::storage::Change;
+use ::config::Config;
+use ::ical;
+use ::dates;
+use ::locale;
+use ::quick_add;
 
 enum CalendarView {
     Year,
     Month,
     Week,
+    Day,
+}
+
+/// What the text typed into the prompt is for.
+enum PromptKind {
+    Import,
+    Search,
+    Command,
+    Goto,
+    Add,
 }
 
+/// How soon a second click on the same slot has to come to count as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(500);
+
 pub struct Calendar {
     view: CalendarView,
+    year_view: YearView,
     month_view: MonthView,
     week_view: WeekView,
+    day_view: DayView,
+    message: Option<String>,
+    db: Arc<Database>,
+    inline_editor: bool,
+    editing: Option<(Slot, TextEditor)>,
+    prompt: Option<(PromptKind, Prompt)>,
+    search: Option<SearchPane>,
+    help: Option<HelpPane>,
+    keymap: Keymap,
+    theme: Theme,
+    /// Keys typed so far of a binding that's more than one key long.
+    pending_keys: Vec<Key>,
+    last_event: Option<(Slot, usize)>,
+    last_recurring: Option<(Slot, usize)>,
+    /// The slot last clicked and when, to tell whether the next click is a double-click.
+    last_click: Option<(Slot, Instant)>,
+    /// A slot double-clicked, to be opened once the button is released so that the release isn't
+    /// read by the editor.
+    double_clicked: Option<Slot>,
+    quit: bool,
 }
 
 impl Calendar {
-    pub fn new(datetime: NaiveDateTime) -> Calendar {
-        let xdg_dirs = xdg::BaseDirectories::with_prefix("callus").unwrap();
-        let path = match xdg_dirs.find_data_file("db") {
-            Some(path) => path,
-            None => xdg_dirs.place_data_file("db").unwrap(),
-        };
-        let db = Arc::new(Database::open(path));
+    pub fn new(datetime: NaiveDateTime, db: Arc<Database>, config: &Config, keymap: Keymap, theme: Theme) -> Calendar {
         Calendar {
             view: CalendarView::Month,
-            month_view: MonthView::new(db.clone(), datetime.date()),
-            week_view: WeekView::new(db.clone(), datetime.date(), datetime.hour() as u8),
+            year_view: YearView::new(db.clone(), theme, datetime.date()),
+            month_view: MonthView::new(db.clone(), theme, datetime.date()),
+            week_view: WeekView::new(db.clone(), theme, datetime.date(), datetime.hour() as u8),
+            day_view: DayView::new(db.clone(), theme, datetime.date(), datetime.hour() as u8),
+            message: None,
+            db,
+            inline_editor: config.inline_editor,
+            editing: None,
+            prompt: None,
+            search: None,
+            help: None,
+            keymap,
+            theme,
+            pending_keys: Vec::new(),
+            last_event: None,
+            last_recurring: None,
+            last_click: None,
+            double_clicked: None,
+            quit: false,
+        }
+    }
+
+    /// Handles a key press outside of any popup, looking it up in the key bindings.
+    pub fn key(&mut self, key: Key) -> bool {
+        if key == Key::Esc && !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            return false;
+        }
+        self.pending_keys.push(key);
+        let view = self.view_name();
+        match self.keymap.lookup(view, &self.pending_keys) {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                self.perform(action)
+            },
+            Lookup::Pending => false,
+            Lookup::Unbound => {
+                let mut keys = self.pending_keys.split_off(0);
+                let last = keys.pop().unwrap();
+                if keys.is_empty() {
+                    return false;
+                }
+                // The keys before this one were waited on in case this one made a longer binding.
+                // They might be a binding themselves, and this key might start another.
+                let mut redraw = false;
+                if let Some(action) = self.keymap.action(view, &keys) {
+                    redraw = self.perform(action);
+                }
+                if self.is_modal() || self.quit {
+                    return redraw;
+                }
+                self.key(last) || redraw
+            },
+        }
+    }
+
+    /// Handles a mouse event outside of any popup. Clicking a slot selects it and double-clicking
+    /// edits it, while the wheel moves by a month in the year view, a week in the month and week
+    /// views and a day in the day view.
+    pub fn mouse(&mut self, event: MouseEvent) -> bool {
+        if self.is_modal() {
+            return false;
+        }
+        match event {
+            // Mouse positions count from 1.
+            MouseEvent::Press(MouseButton::Left, x, y) => {
+                let slot = match self.slot_at(x.saturating_sub(1), y.saturating_sub(1)) {
+                    Some(slot) => slot,
+                    None => return false,
+                };
+                self.pending_keys.clear();
+                match self.last_click {
+                    Some((last, at)) if last == slot && at.elapsed() < DOUBLE_CLICK => {
+                        self.last_click = None;
+                        self.double_clicked = Some(slot);
+                    },
+                    _ => {
+                        self.last_click = Some((slot, Instant::now()));
+                        self.select(slot);
+                    },
+                }
+                false
+            },
+            MouseEvent::Release(..) => match self.double_clicked.take() {
+                Some(_) => self.enter(),
+                None => false,
+            },
+            MouseEvent::Press(MouseButton::WheelUp, ..) => self.scroll(-1),
+            MouseEvent::Press(MouseButton::WheelDown, ..) => self.scroll(1),
+            _ => false,
+        }
+    }
+
+    /// The slot drawn at `x`, `y` in the open view.
+    fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        match self.view {
+            Year => self.year_view.slot_at(x, y),
+            Month => self.month_view.slot_at(x, y),
+            Week => self.week_view.slot_at(x, y),
+            Day => self.day_view.slot_at(x, y),
+        }
+    }
+
+    /// Selects a slot in the open view without scrolling it more than needed.
+    fn select(&mut self, slot: Slot) {
+        let date = match slot {
+            Slot::Day(date) | Slot::Hour(date, _) => date,
+        };
+        match self.view {
+            Year => self.year_view.set_date(date),
+            Month => self.month_view.set_date(date),
+            Week => self.week_view.select(date, match slot {
+                Slot::Hour(_, hour) => hour,
+                Slot::Day(_) => self.week_view.get_hour(),
+            }),
+            Day => self.day_view.set_slot(slot),
+        }
+    }
+
+    /// Moves the selection for a turn of the mouse wheel, towards the future for positive `steps`.
+    fn scroll(&mut self, steps: i32) -> bool {
+        let days = match self.view {
+            Year => return self.page(steps),
+            Month | Week => 7 * steps,
+            Day => steps,
+        };
+        let date = self.selected_date() + one_day() * days;
+        self.move_to(date);
+        false
+    }
+
+    fn perform(&mut self, action: Action) -> bool {
+        match action {
+            Action::Left => self.left(),
+            Action::Right => self.right(),
+            Action::Up => self.up(),
+            Action::Down => self.down(),
+            Action::Edit => self.enter(),
+            Action::EditInline => self.edit_inline(),
+            Action::EditExternal => self.edit_external(),
+            Action::NewEvent => self.new_event(),
+            Action::EditEvent => self.edit_event(),
+            Action::NewRecurring => self.new_recurring(),
+            Action::EditRecurring => self.edit_recurring(),
+            Action::SkipOccurrence => self.skip_occurrence(),
+            Action::Import => self.import(),
+            Action::Search => self.search(),
+            Action::Command => self.command(),
+            Action::NextView => self.next_view(),
+            Action::PrevView => self.prev_view(),
+            Action::Today => self.today(),
+            Action::Goto => self.goto_prompt(),
+            Action::Add => self.add(),
+            Action::NextMonth => self.page(1),
+            Action::PrevMonth => self.page(-1),
+            Action::NextYear => self.page(12),
+            Action::PrevYear => self.page(-12),
+            Action::Help => self.help(),
+            Action::Quit => {
+                self.quit = true;
+                false
+            },
+            Action::None => false,
+        }
+    }
+
+    /// The name of the open view as used in the config file.
+    fn view_name(&self) -> &'static str {
+        match self.view {
+            Year => "year",
+            Month => "month",
+            Week => "week",
+            Day => "day",
         }
     }
 
+    /// Selects today, and the current hour in views that show hours.
+    pub fn today(&mut self) -> bool {
+        let now = Local::now().naive_local();
+        self.move_to(now.date());
+        self.week_view.set_hour(now.hour() as u8);
+        self.day_view.set_date(now.date(), now.hour() as u8);
+        false
+    }
+
+    /// Opens a prompt for a date to jump to.
+    pub fn goto_prompt(&mut self) -> bool {
+        self.prompt = Some((PromptKind::Goto, Prompt::new("Go to")));
+        false
+    }
+
+    /// Opens a prompt for an entry to add, with its date and time written in it.
+    pub fn add(&mut self) -> bool {
+        self.prompt = Some((PromptKind::Add, Prompt::new("Add")));
+        false
+    }
+
+    fn run_add(&mut self, input: &str) {
+        let added = match quick_add::parse(input, Local::now().naive_local()) {
+            Ok(added) => added,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            },
+        };
+        if let Err(e) = self.db.append(added.slot, &added.text) {
+            self.message = Some(e.to_string());
+            return;
+        }
+        self.goto(added.slot);
+        self.message = Some(format!("added to {}", slot_title(added.slot)));
+    }
+
+    /// Moves the selection forwards or backwards by a number of months.
+    pub fn page(&mut self, months: i32) -> bool {
+        if let Some(date) = dates::add_months(self.selected_date(), months) {
+            self.move_to(date);
+        }
+        false
+    }
+
+    fn run_goto(&mut self, input: &str) {
+        match dates::parse(input, Local::now().naive_local().date()) {
+            Some(date) => self.move_to(date),
+            None => {
+                self.message = Some(format!("can't read `{}` as a date, try eg. `2025-03-14`, `next friday`, `+3w` or `dec 25`",
+                                            input));
+            },
+        }
+    }
+
+    fn selected_date(&self) -> NaiveDate {
+        match self.selected_slot() {
+            Some(Slot::Day(date)) | Some(Slot::Hour(date, _)) => date,
+            None => self.year_view.get_date(),
+        }
+    }
+
+    /// Selects `date` in every view, keeping the open view and the selected hour.
+    fn move_to(&mut self, date: NaiveDate) {
+        let hour = self.week_view.get_hour();
+        self.year_view.set_date(date);
+        self.month_view.set_date(date);
+        self.week_view.set_date(date);
+        self.week_view.set_hour(hour);
+        self.day_view.set_slot(match self.day_view.get_hour() {
+            Some(hour) => Slot::Hour(date, hour),
+            None => Slot::Day(date),
+        });
+    }
+
+    /// Shows the key bindings of the open view.
+    pub fn help(&mut self) -> bool {
+        let view = self.view_name();
+        self.help = Some(HelpPane::new(String::from(view), self.keymap.bindings(view)));
+        false
+    }
+
     pub fn next_view(&mut self) -> bool {
         self.view = match self.view {
-            Year => Month,
+            Year => {
+                self.month_view.set_date(self.year_view.get_date());
+                Month
+            },
             Month => {
                 self.week_view.set_date(self.month_view.get_date());
                 Week
             },
-            Week => Week,
+            Week => {
+                self.day_view.set_date(self.week_view.get_date(), self.week_view.get_hour());
+                Day
+            },
+            Day => Day,
         };
         false
     }
@@ -55,89 +367,539 @@ impl Calendar {
     pub fn prev_view(&mut self) -> bool {
         self.view = match self.view {
             Year => Year,
-            //Month => Year,
-            Month => Month,
+            Month => {
+                self.year_view.set_date(self.month_view.get_date());
+                Year
+            },
             Week => {
                 self.month_view.set_date(self.week_view.get_date());
                 Month
             },
+            Day => {
+                self.week_view.set_date(self.day_view.get_date());
+                if let Some(hour) = self.day_view.get_hour() {
+                    self.week_view.set_hour(hour);
+                }
+                Week
+            },
         };
         false
     }
 
     pub fn up(&mut self) -> bool {
         match self.view {
+            Year => {
+                self.year_view.up()
+            },
             Month => {
                 self.month_view.up()
             },
             Week => {
                 self.week_view.up()
             },
-            _ => unimplemented!(),
+            Day => {
+                self.day_view.up()
+            },
         }
     }
 
     pub fn down(&mut self) -> bool {
         match self.view {
+            Year => {
+                self.year_view.down()
+            },
             Month => {
                 self.month_view.down()
             },
             Week => {
                 self.week_view.down()
             },
-            _ => unimplemented!(),
+            Day => {
+                self.day_view.down()
+            },
         }
     }
 
     pub fn right(&mut self) -> bool {
         match self.view {
+            Year => {
+                self.year_view.right()
+            },
             Month => {
                 self.month_view.right()
             },
             Week => {
                 self.week_view.right()
             },
-            _ => unimplemented!(),
+            Day => {
+                self.day_view.right()
+            },
         }
     }
 
     pub fn left(&mut self) -> bool {
         match self.view {
+            Year => {
+                self.year_view.left()
+            },
             Month => {
                 self.month_view.left()
             },
             Week => {
                 self.week_view.left()
             },
-            _ => unimplemented!(),
+            Day => {
+                self.day_view.left()
+            },
         }
     }
 
     pub fn enter(&mut self) -> bool {
+        match self.inline_editor {
+            true => self.edit_inline(),
+            false => self.edit_external(),
+        }
+    }
+
+    fn selected_slot(&self) -> Option<Slot> {
         match self.view {
-            Month => {
-                self.month_view.enter()
+            Year => None,
+            Month => Some(self.month_view.selected_slot()),
+            Week => Some(self.week_view.selected_slot()),
+            Day => Some(self.day_view.selected_slot()),
+        }
+    }
+
+    pub fn edit_external(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return self.next_view(),
+        };
+        if let Err(e) = self.db.edit(slot) {
+            self.message = Some(e.to_string());
+        }
+        true
+    }
+
+    pub fn edit_inline(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return self.next_view(),
+        };
+        match self.db.get(slot) {
+            Ok(text) => self.editing = Some((slot, TextEditor::new(slot_title(slot), &text))),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        false
+    }
+
+    pub fn new_event(&mut self) -> bool {
+        let res = match self.selected_slot() {
+            Some(Slot::Day(date)) => self.db.new_event(date, 9),
+            Some(Slot::Hour(date, hour)) => self.db.new_event(date, hour),
+            None => return false,
+        };
+        if let Err(e) = res {
+            self.message = Some(e.to_string());
+        }
+        true
+    }
+
+    /// Opens one of the events at the selected slot in the editor. Pressing this repeatedly on
+    /// the same slot cycles through its events.
+    pub fn edit_event(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let events = match slot {
+            Slot::Day(date) => self.db.get_events(date),
+            Slot::Hour(date, hour) => self.db.get_hour_events(date, hour),
+        };
+        if events.is_empty() {
+            self.message = Some(String::from("no events here, press n to add one"));
+            return false;
+        }
+        let index = match self.last_event {
+            Some((last_slot, index)) if last_slot == slot => (index + 1) % events.len(),
+            _ => 0,
+        };
+        self.last_event = Some((slot, index));
+        if let Err(e) = self.db.edit_event(&events[index]) {
+            self.message = Some(e.to_string());
+        }
+        true
+    }
+
+    pub fn new_recurring(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        if let Err(e) = self.db.new_recurring(slot) {
+            self.message = Some(e.to_string());
+        }
+        true
+    }
+
+    /// Opens one of the recurring entries at the selected slot in the editor, cycling through
+    /// them like `edit_event`.
+    pub fn edit_recurring(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let recurring = self.db.get_recurring(slot);
+        if recurring.is_empty() {
+            self.message = Some(String::from("no recurring entries here, press r to add one"));
+            return false;
+        }
+        let index = match self.last_recurring {
+            Some((last_slot, index)) if last_slot == slot => (index + 1) % recurring.len(),
+            _ => 0,
+        };
+        self.last_recurring = Some((slot, index));
+        if let Err(e) = self.db.edit_recurring(&recurring[index]) {
+            self.message = Some(e.to_string());
+        }
+        true
+    }
+
+    /// Removes the recurring entries at the selected slot from that day only.
+    pub fn skip_occurrence(&mut self) -> bool {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let day = match slot {
+            Slot::Day(day) | Slot::Hour(day, _) => day,
+        };
+        let recurring = self.db.get_recurring(slot);
+        if recurring.is_empty() {
+            self.message = Some(String::from("no recurring entries here"));
+            return false;
+        }
+        for r in &recurring {
+            if let Err(e) = self.db.skip_occurrence(r, day) {
+                self.message = Some(e.to_string());
+                return true;
+            }
+        }
+        self.message = Some(format!("skipped {} recurring entr{} on this day",
+                                    recurring.len(), if recurring.len() == 1 { "y" } else { "ies" }));
+        true
+    }
+
+    pub fn import(&mut self) -> bool {
+        self.prompt = Some((PromptKind::Import, Prompt::new("Import .ics file")));
+        false
+    }
+
+    pub fn search(&mut self) -> bool {
+        self.prompt = Some((PromptKind::Search, Prompt::new("Search")));
+        false
+    }
+
+    /// Opens the `:` command line.
+    pub fn command(&mut self) -> bool {
+        self.prompt = Some((PromptKind::Command, Prompt::command()));
+        false
+    }
+
+    /// Whether `:quit` has been entered.
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Runs a command typed into the `:` command line.
+    fn run_command(&mut self, input: &str) {
+        let (command, arg) = match input.find(char::is_whitespace) {
+            Some(i) => (&input[..i], input[i..].trim()),
+            None => (input, ""),
+        };
+        match command {
+            "" => (),
+            "q" | "quit" => self.quit = true,
+            "year" => self.set_view(Year),
+            "month" => self.set_view(Month),
+            "week" => self.set_view(Week),
+            "day" => self.set_view(Day),
+            "today" => {
+                self.today();
             },
-            Week => {
-                self.week_view.enter()
+            "goto" | "g" if !arg.is_empty() => self.run_goto(arg),
+            "add" if !arg.is_empty() => self.run_add(arg),
+            "search" if !arg.is_empty() => self.run_search(arg),
+            "import" if !arg.is_empty() => self.run_import(arg),
+            "reload" => {
+                self.db.reload();
+                self.message = Some(String::from("reloaded"));
+            },
+            "goto" | "g" => self.message = Some(String::from("usage: :goto DATE")),
+            "add" => self.message = Some(String::from("usage: :add TEXT")),
+            "search" => self.message = Some(String::from("usage: :search QUERY")),
+            "import" => self.message = Some(String::from("usage: :import FILE")),
+            _ => self.message = Some(format!("unknown command `{}`", command)),
+        }
+    }
+
+    /// Switches to another view, keeping the selected date.
+    fn set_view(&mut self, view: CalendarView) {
+        let slot = match self.selected_slot() {
+            Some(slot) => slot,
+            None => Slot::Day(self.year_view.get_date()),
+        };
+        self.goto(slot);
+        self.view = view;
+    }
+
+    fn run_import(&mut self, path: &str) {
+        self.message = Some(match ical::import(&self.db, &expand_home(path)) {
+            Ok(summary) if summary.skipped > 0 => {
+                format!("imported {} new, {} updated and {} unchanged events, skipped {} with unsupported RRULEs",
+                        summary.added, summary.updated, summary.unchanged, summary.skipped)
             },
-            _ => unimplemented!(),
+            Ok(summary) => format!("imported {} new, {} updated and {} unchanged events",
+                                   summary.added, summary.updated, summary.unchanged),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    fn run_search(&mut self, query: &str) {
+        let results = self.db.search(query);
+        self.message = Some(match results.len() {
+            0 => format!("no matches for \"{}\"", query),
+            1 => format!("1 match for \"{}\"", query),
+            n => format!("{} matches for \"{}\"", n, query),
+        });
+        if !results.is_empty() {
+            self.search = Some(SearchPane::new(String::from(query), results));
+        }
+    }
+
+    /// Moves every view to the given slot, so that it's selected whichever view is shown.
+    fn goto(&mut self, slot: Slot) {
+        let date = match slot {
+            Slot::Day(date) | Slot::Hour(date, _) => date,
+        };
+        self.year_view.set_date(date);
+        self.month_view.set_date(date);
+        self.week_view.set_date(date);
+        if let Slot::Hour(_, hour) = slot {
+            self.week_view.set_hour(hour);
+        }
+        self.day_view.set_slot(slot);
+        if let Year = self.view {
+            self.view = Month;
         }
     }
+
+    /// Whether a popup editor, prompt, search results or the help are open and should be sent all
+    /// key presses.
+    pub fn is_modal(&self) -> bool {
+        self.editing.is_some() || self.prompt.is_some() || self.search.is_some() || self.help.is_some()
+    }
+
+    pub fn modal_key(&mut self, key: Key) -> bool {
+        if let Some(ref mut help) = self.help {
+            if help.key(key) {
+                self.help = None;
+            }
+            return false;
+        }
+        if self.prompt.is_some() {
+            return self.prompt_key(key);
+        }
+        if let Some(ref mut search) = self.search {
+            match search.key(key) {
+                SearchAction::Continue => (),
+                SearchAction::Close => self.search = None,
+                SearchAction::Jump(slot) => {
+                    self.search = None;
+                    self.goto(slot);
+                },
+            }
+            return false;
+        }
+        let (slot, text) = match self.editing {
+            Some((slot, ref mut editor)) => match editor.key(key) {
+                EditResult::Continue => return false,
+                EditResult::Cancel => (slot, None),
+                EditResult::Save(text) => (slot, Some(text)),
+            },
+            None => return false,
+        };
+        self.editing = None;
+        if let Some(text) = text {
+            match self.db.set(slot, &text) {
+                Ok(()) => self.message = Some(String::from("saved")),
+                Err(e) => self.message = Some(e.to_string()),
+            }
+        }
+        false
+    }
+
+    fn prompt_key(&mut self, key: Key) -> bool {
+        let input = match self.prompt {
+            Some((_, ref mut prompt)) => match prompt.key(key) {
+                PromptResult::Continue => return false,
+                PromptResult::Cancel => None,
+                PromptResult::Submit(input) => Some(input),
+            },
+            None => return false,
+        };
+        let (kind, _) = self.prompt.take().unwrap();
+        let input = match input {
+            Some(input) => input,
+            None => return false,
+        };
+        match kind {
+            PromptKind::Import => self.run_import(input.trim()),
+            PromptKind::Search if !input.trim().is_empty() => self.run_search(input.trim()),
+            PromptKind::Search => (),
+            PromptKind::Command => self.run_command(input.trim()),
+            PromptKind::Goto if !input.trim().is_empty() => self.run_goto(input.trim()),
+            PromptKind::Goto => (),
+            PromptKind::Add if !input.trim().is_empty() => self.run_add(input.trim()),
+            PromptKind::Add => (),
+        }
+        false
+    }
+
+    /// Shows the error if reading from storage failed while drawing. Returns whether there was
+    /// one.
+    pub fn show_storage_error(&mut self) -> bool {
+        match self.db.take_error() {
+            Some(e) => {
+                self.message = Some(e.to_string());
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Starts calling `changed` when entries are changed from outside callus. If they can't be
+    /// watched, says so and carries on without.
+    pub fn watch_storage<F: Fn(Change) + Send + 'static>(&mut self, changed: F) -> Option<RecommendedWatcher> {
+        match self.db.watch(changed) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                self.message = Some(format!("not watching for changes: {}", e));
+                None
+            },
+        }
+    }
+
+    /// Forgets the entries covered by a change made outside callus so that the new ones are
+    /// drawn.
+    pub fn storage_changed(&mut self, change: Change) {
+        self.db.invalidate(change);
+    }
+
+    /// Shows a notification, such as a reminder, on the status line.
+    pub fn notify(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    pub fn status_bar(&self) -> StatusBar<'_> {
+        let (view, day, position) = match self.view {
+            Year => {
+                let date = self.year_view.get_date();
+                ("Year", date, locale::get().date_title(date))
+            },
+            _ => {
+                let slot = self.selected_slot().unwrap();
+                let view = match self.view {
+                    Month => "Month",
+                    Week => "Week",
+                    _ => "Day",
+                };
+                match slot {
+                    Slot::Day(date) | Slot::Hour(date, _) => (view, date, slot_title(slot)),
+                }
+            },
+        };
+        StatusBar::new(view, position, self.db.count_entries(day), self.theme)
+            .message(self.message.as_ref().map(|s| &s[..]))
+            .hints(self.hints())
+            .prompt(self.prompt.as_ref().map(|(_, prompt)| prompt))
+    }
+
+    /// The keys typed so far of a longer binding, or else the keys for the most used actions.
+    fn hints(&self) -> String {
+        if !self.pending_keys.is_empty() {
+            return keys::keys_name(&self.pending_keys);
+        }
+        let view = self.view_name();
+        let hints = [
+            (Action::Edit, "edit"),
+            (Action::NewEvent, "event"),
+            (Action::Search, "search"),
+            (Action::Command, "command"),
+            (Action::Help, "help"),
+            (Action::Quit, "quit"),
+        ];
+        hints.iter()
+            .filter_map(|&(action, hint)| {
+                let key = self.keymap.first_key(view, action)?;
+                let key = match key.len() > 2 && key.starts_with('<') && key.ends_with('>') {
+                    true => &key[1..key.len() - 1],
+                    false => &key[..],
+                };
+                Some(format!("{} {}", key, hint))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
 }
 
 impl Widget for Calendar {
     fn draw(&self, area: &Rect, buffer: &mut Buffer) {
         match self.view {
+            Year => {
+                self.year_view.draw(area, buffer)
+            },
             Month => {
                 self.month_view.draw(area, buffer)
             },
             Week => {
                 self.week_view.draw(area, buffer)
             },
-            _ => unimplemented!(),
+            Day => {
+                self.day_view.draw(area, buffer)
+            },
+        }
+        if let Some(ref search) = self.search {
+            search.draw(area, buffer, &self.theme);
+        }
+        if let Some((_, ref editor)) = self.editing {
+            editor.draw(area, buffer, &self.theme);
+        }
+        if let Some(ref help) = self.help {
+            help.draw(area, buffer, &self.theme);
         }
     }
 }
 
+pub fn slot_title(slot: Slot) -> String {
+    let locale = locale::get();
+    match slot {
+        Slot::Day(date) => locale.date_title(date),
+        Slot::Hour(date, hour) => format!("{} {}", hour_name(hour), locale.date_title(date)),
+    }
+}
+
+/// Expands a leading `~/` in a path typed by the user.
+fn expand_home(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = env::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
 
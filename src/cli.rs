@@ -0,0 +1,231 @@
+use chrono::naive::date::NaiveDate;
+use chrono::offset::local::Local;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use ::agenda;
+use ::calendar::slot_title;
+use ::config::{Config, Sink};
+use ::database::{Database, Slot};
+use ::error::Error;
+use ::ical;
+use ::locale;
+use ::migrate;
+use ::quick_add;
+use ::remind;
+use ::storage::DirStorage;
+use ::one_day;
+
+pub fn usage() {
+    println!("Usage: callus [COMMAND]");
+    println!();
+    println!("With no command, opens the calendar.");
+    println!();
+    println!("Commands:");
+    println!("    import FILE...    Import the events from iCalendar (.ics) files");
+    println!("    export --from DATE --to DATE [-o FILE]");
+    println!("                      Export entries and events as an iCalendar file");
+    println!("    today [--json]    Print today's entries and events");
+    println!("    tomorrow [--json] Print tomorrow's entries and events");
+    println!("    week [--json]     Print this week's entries and events");
+    println!("    agenda --from DATE --to DATE [--json]");
+    println!("                      Print the entries and events between two dates");
+    println!("    search QUERY      Print the entries and events containing QUERY");
+    println!("    add TEXT...       Add an entry, eg. `callus add lunch with Sam tomorrow 1pm`");
+    println!("    migrate [--dry-run] [--no-backup]");
+    println!("                      Update the storage layout, backing it up first");
+    println!("    import-dir [DIR]  Copy a directory database, by default the one in the data directory,");
+    println!("                      into the configured storage backend");
+    println!("    remind [--lead MINUTES] [--sink stdout|command|dbus] [--once]");
+    println!("                      Send reminders for upcoming hour entries and events");
+}
+
+/// Adds an entry written as one line, putting it on the date and at the time it mentions.
+pub fn add(db: &Database, args: &[String]) -> Result<(), Error> {
+    if args.is_empty() {
+        return Err(Error::Usage(String::from("callus add TEXT...")));
+    }
+    let added = quick_add::parse(&args.join(" "), Local::now().naive_local()).map_err(Error::Invalid)?;
+    db.append(added.slot, &added.text)?;
+    println!("added to {}: {}", slot_title(added.slot), added.text);
+    Ok(())
+}
+
+pub fn import(db: &Database, args: &[String]) -> Result<(), Error> {
+    if args.is_empty() {
+        return Err(Error::Usage(String::from("callus import FILE...")));
+    }
+    for arg in args {
+        let summary = ical::import(db, Path::new(arg))?;
+        println!("{}: {} new, {} updated, {} unchanged", arg, summary.added, summary.updated, summary.unchanged);
+        if summary.skipped > 0 {
+            println!("{}: skipped {} repeating events with unsupported RRULEs", arg, summary.skipped);
+        }
+    }
+    Ok(())
+}
+
+pub fn export(db: &Database, args: &[String]) -> Result<(), Error> {
+    let usage = || Error::Usage(String::from("callus export --from DATE --to DATE [-o FILE]"));
+    let mut from = None;
+    let mut to = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--from" => from = Some(parse_date(args.next().ok_or_else(usage)?)?),
+            "--to" => to = Some(parse_date(args.next().ok_or_else(usage)?)?),
+            "-o" | "--output" => output = Some(args.next().ok_or_else(usage)?),
+            _ => return Err(usage()),
+        }
+    }
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return Err(usage()),
+    };
+
+    let count = match output {
+        Some(path) => {
+            let mut f = File::create(path)?;
+            ical::export(db, from, to, &mut f)?
+        },
+        None => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let count = ical::export(db, from, to, &mut stdout)?;
+            stdout.flush()?;
+            count
+        },
+    };
+    eprintln!("exported {} events", count);
+    Ok(())
+}
+
+pub fn remind(db: &Database, config: &Config, args: &[String]) -> Result<(), Error> {
+    let usage = || Error::Usage(String::from("callus remind [--lead MINUTES] [--sink stdout|command|dbus] [--once]"));
+    let mut remind_config = config.remind.clone();
+    let mut once = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--lead" => {
+                let minutes = args.next().ok_or_else(usage)?;
+                remind_config.lead_minutes = minutes.parse().map_err(|_| usage())?;
+            },
+            "--sink" => remind_config.sink = match args.next().map(|s| &s[..]) {
+                Some("stdout") => Sink::Stdout,
+                Some("command") => Sink::Command,
+                Some("dbus") => Sink::Dbus,
+                _ => return Err(usage()),
+            },
+            "--once" => once = true,
+            _ => return Err(usage()),
+        }
+    }
+    Ok(remind::run(db, &remind_config, once)?)
+}
+
+/// Prints the agenda for `today`, `tomorrow`, `week` or, with `--from` and `--to`, `agenda`.
+pub fn agenda(db: &Database, command: &str, args: &[String]) -> Result<(), Error> {
+    let usage = || Error::Usage(match command {
+        "agenda" => String::from("callus agenda --from DATE --to DATE [--json]"),
+        _ => format!("callus {} [--json]", command),
+    });
+    let mut from = None;
+    let mut to = None;
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--from" if command == "agenda" => from = Some(parse_date(args.next().ok_or_else(usage)?)?),
+            "--to" if command == "agenda" => to = Some(parse_date(args.next().ok_or_else(usage)?)?),
+            "--json" => json = true,
+            _ => return Err(usage()),
+        }
+    }
+
+    let today = Local::now().naive_local().date();
+    let (from, to) = match (command, from, to) {
+        ("today", _, _) => (today, today),
+        ("tomorrow", _, _) => (today + one_day(), today + one_day()),
+        ("week", _, _) => {
+            let first = locale::get().week_start(today);
+            (first, first + one_day() * 6)
+        },
+        (_, Some(from), Some(to)) => (from, to),
+        _ => return Err(usage()),
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    agenda::print(db, from, to, json, &mut stdout)?;
+    Ok(stdout.flush()?)
+}
+
+pub fn search(db: &Database, args: &[String]) -> Result<(), Error> {
+    if args.is_empty() {
+        return Err(Error::Usage(String::from("callus search QUERY")));
+    }
+    let query = args.join(" ");
+    for result in db.search(&query) {
+        match result.slot {
+            Slot::Day(day) => println!("{}        {}", day.format("%Y-%m-%d"), result.line),
+            Slot::Hour(day, hour) => println!("{} {:02}:00  {}", day.format("%Y-%m-%d"), hour, result.line),
+        }
+    }
+    Ok(())
+}
+
+/// Copies everything in a directory database into `db`'s storage, eg. to move to the sqlite
+/// backend.
+pub fn import_dir(db: &Database, from: &DirStorage) -> Result<(), Error> {
+    let (entries, events, recurring) = db.import_from(from)?;
+    println!("copied {} entries, {} events and {} recurring entries", entries, events, recurring);
+    Ok(())
+}
+
+pub fn migrate(location: &Path, args: &[String]) -> Result<(), Error> {
+    let mut dry_run = false;
+    let mut backup = true;
+    for arg in args {
+        match &arg[..] {
+            "--dry-run" | "-n" => dry_run = true,
+            "--no-backup" => backup = false,
+            _ => return Err(Error::Usage(String::from("callus migrate [--dry-run] [--no-backup]"))),
+        }
+    }
+    let plan = migrate::plan(location)?;
+    if plan.version == migrate::STORAGE_VERSION {
+        println!("{} is already at storage version {}", location.display(), plan.version);
+        return Ok(());
+    }
+    if plan.version > migrate::STORAGE_VERSION {
+        return Err(Error::Invalid(format!(
+            "{} uses storage version {}, which is newer than this callus", location.display(), plan.version)));
+    }
+    for rename in &plan.renames {
+        println!("rename {} -> {}", rename.from.display(), rename.to.display());
+    }
+    for conflict in &plan.conflicts {
+        println!("skip {}: it clashes with {}", conflict.from.display(), conflict.to.display());
+    }
+    if dry_run {
+        println!("dry run: would migrate from storage version {} to {}", plan.version, migrate::STORAGE_VERSION);
+        return Ok(());
+    }
+    if backup {
+        println!("backed up to {}", migrate::backup(location)?.display());
+    }
+    migrate::apply(location, &plan)?;
+    println!("migrated from storage version {} to {}", plan.version, migrate::STORAGE_VERSION);
+    Ok(())
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| {
+        Error::Invalid(format!("invalid date `{}` ({}), expected eg. `2017-05-31`", s, e))
+    })
+}
+
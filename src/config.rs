@@ -0,0 +1,141 @@
+use xdg;
+use toml;
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::fs::File;
+use std::path::PathBuf;
+
+use ::error::Error;
+
+/// User settings read from `$XDG_CONFIG_HOME/callus/config.toml`. Every setting is optional.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Command used to edit entries, eg. `"emacsclient -t"`. `{file}` and `{line}` are replaced
+    /// with the entry's path and last line. If `{file}` is not present the path is appended.
+    pub editor: Option<String>,
+    /// Edit entries in a popup inside callus when Enter is pressed, rather than in the editor.
+    pub inline_editor: bool,
+    pub remind: RemindConfig,
+    pub storage: StorageConfig,
+    pub keys: KeysConfig,
+    pub theme: ThemeConfig,
+    pub locale: LocaleConfig,
+}
+
+/// The `[keys]` section, mapping key sequences to actions, eg. `"gg" = "today"` or
+/// `"<C-n>" = "next_view"`. Bindings in `[keys.month]` and the other views' tables only apply in
+/// that view. Binding a key to `"none"` unbinds it.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct KeysConfig {
+    pub year: HashMap<String, String>,
+    pub month: HashMap<String, String>,
+    pub week: HashMap<String, String>,
+    pub day: HashMap<String, String>,
+    #[serde(flatten)]
+    pub global: HashMap<String, String>,
+}
+
+/// The `[theme]` section: `name` picks a built-in theme, `default`, `light` or `monochrome`, and
+/// any of its styles can be replaced, eg. `selected = "black on yellow"` or `today = "bold"`.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub styles: HashMap<String, String>,
+}
+
+/// The `[locale]` section. Without it the language and the day weeks start on follow
+/// `$LC_ALL`, `$LC_TIME` or `$LANG`, falling back to English and Monday.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// The language of day and month names: `en`, `de`, `fr`, `es`, `it`, `nl`, `pt` or `sv`.
+    pub language: Option<String>,
+    /// The day weeks start on, eg. `"sunday"`.
+    pub week_start: Option<String>,
+    /// Names to use instead of the language's, seven starting with Monday.
+    pub day_names: Option<Vec<String>>,
+    /// Twelve names to use instead of the language's. Entries are still stored under the English
+    /// names.
+    pub month_names: Option<Vec<String>>,
+}
+
+/// The `[storage]` section.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: Backend,
+    /// The file used by the `journal` and `sqlite` backends, instead of the default in
+    /// `$XDG_DATA_HOME/callus`.
+    pub path: Option<PathBuf>,
+}
+
+/// Where entries are kept.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A directory of plain text files under `$XDG_DATA_HOME/callus/db`.
+    #[default]
+    Dir,
+    /// A single SQLite database at `$XDG_DATA_HOME/callus/db.sqlite`. Only available when callus
+    /// is built with the `sqlite` feature.
+    Sqlite,
+    /// A single text file, `$XDG_DATA_HOME/callus/journal.md`, with a `## YYYY-MM-DD` heading
+    /// for each day and a `### HH:00` heading for each hour.
+    Journal,
+    /// Nothing is saved once callus exits.
+    Memory,
+}
+
+/// The `[remind]` section, used by `callus remind`.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct RemindConfig {
+    /// How many minutes before an entry starts to send its reminder.
+    pub lead_minutes: u32,
+    pub sink: Sink,
+    /// Command run for each reminder when `sink = "command"`. `{time}`, `{title}` and `{body}`
+    /// are replaced with the reminder's details. If none of them are present the title and body
+    /// are appended.
+    pub command: Option<String>,
+}
+
+impl Default for RemindConfig {
+    fn default() -> RemindConfig {
+        RemindConfig {
+            lead_minutes: 10,
+            sink: Sink::Stdout,
+            command: None,
+        }
+    }
+}
+
+/// Where reminders are sent.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sink {
+    Stdout,
+    Command,
+    /// A desktop notification, sent over the D-Bus session bus.
+    Dbus,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, Error> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("callus")?;
+        let path = match xdg_dirs.find_config_file("config.toml") {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let mut s = String::new();
+        let mut f = File::open(&path)?;
+        f.read_to_string(&mut s)?;
+        toml::from_str(&s).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+}
+
@@ -1,95 +1,777 @@
 use chrono::naive::date::NaiveDate;
-use chrono::Datelike;
+use chrono::Timelike;
 
-use std::path::{Path, PathBuf};
-use std::fs::File;
-use std::io::Read;
+use chrono::naive::date;
+use notify::RecommendedWatcher;
+
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::collections::{hash_map, HashMap};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::process;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ::editor::Editor;
+use ::error::Error;
+use ::event::{self, Event};
+use ::recurrence::Recurring;
+use ::storage::{self, Change, Storage};
+use ::one_day;
+
+/// A single editable entry: either a day's note or one hour of a day.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Slot {
+    Day(NaiveDate),
+    Hour(NaiveDate, u8),
+}
+
+/// An entry, event or recurring entry whose text matches a search.
+pub struct SearchResult {
+    pub slot: Slot,
+    /// The first line that matched.
+    pub line: String,
+}
 
-use ::editor;
-use ::{MONTH_NAMES};
+/// Events are stored under the day they start on, so finding the events that are still going on a
+/// day means looking this many days back. Events that last longer are all loaded at once and kept
+/// apart, see `is_long`.
+const MAX_EVENT_DAYS: i32 = 7;
 
 pub struct Database {
-    location: PathBuf,
+    storage: Box<dyn Storage>,
     days: Mutex<HashMap<NaiveDate, String>>,
     hours: Mutex<HashMap<(NaiveDate, u8), String>>,
+    events: Mutex<HashMap<NaiveDate, Vec<Event>>>,
+    long_events: Mutex<Option<Vec<Event>>>,
+    recurring: Mutex<Option<Vec<Recurring>>>,
+    occurrences: Mutex<HashMap<NaiveDate, Vec<Recurring>>>,
+    /// The first storage error hit while reading, waiting to be shown.
+    error: Mutex<Option<Error>>,
+    editor: Editor,
 }
 
 impl Database {
-    pub fn open(location: PathBuf) -> Database {
-        println!("got dir: {:?}", location);
+    pub fn new(storage: Box<dyn Storage>, editor: Editor) -> Database {
         Database {
-            location: location,
+            storage,
             days: Mutex::new(HashMap::new()),
             hours: Mutex::new(HashMap::new()),
+            events: Mutex::new(HashMap::new()),
+            long_events: Mutex::new(None),
+            recurring: Mutex::new(None),
+            occurrences: Mutex::new(HashMap::new()),
+            error: Mutex::new(None),
+            editor,
         }
     }
 
-    fn day_filename(&self, day: NaiveDate) -> PathBuf {
-        let mut path = self.location.clone();
-        path.push(format!("{}", day.year()));
-        path.push(format!("{}", MONTH_NAMES[day.month0() as usize]));
-        path.push(format!("{}", day.day()));
-        path.push("today.txt");
-        path
-    }
-
-    fn hour_filename(&self, day: NaiveDate, hour: u8) -> PathBuf {
-        let mut path = self.location.clone();
-        path.push(format!("{}", day.year()));
-        path.push(format!("{}", MONTH_NAMES[day.month0() as usize]));
-        path.push(format!("{}", day.day()));
-        path.push(format!("{}{}", hour, if hour < 12 { "AM" } else { "PM" }));
-        path
+    /// Returns the day's note followed by any recurring entries that fall on the day.
+    pub fn get_day(&self, day: NaiveDate) -> String {
+        let texts = self.recurring_on(day)
+            .into_iter()
+            .filter(|recurring| recurring.hour.is_none())
+            .map(|recurring| recurring.text);
+        join_entries(self.report(self.get_day_entry(day)), texts)
     }
 
-    pub fn get_day(&self, day: NaiveDate) -> String {
+    /// Returns the day's own note, without recurring entries. A note that can't be read isn't
+    /// kept, so that it's read again next time.
+    pub fn get_day_entry(&self, day: NaiveDate) -> Result<String, io::Error> {
         let mut days = self.days.lock().unwrap();
         match days.entry(day) {
-            hash_map::Entry::Occupied(oe) => oe.get().to_owned(),
+            hash_map::Entry::Occupied(oe) => Ok(oe.get().to_owned()),
             hash_map::Entry::Vacant(ve) => {
-                let path = self.day_filename(day);
-                ve.insert(load_entry(&path)).clone()
+                Ok(ve.insert(self.storage.get(Slot::Day(day))?).clone())
             },
         }
     }
 
-    pub fn edit_day(&self, day: NaiveDate) {
-        let path = self.day_filename(day);
-        editor::edit(&path);
-        let mut days = self.days.lock().unwrap();
-        days.insert(day, load_entry(&path));
+    /// Returns the hour's entry followed by any recurring entries that fall on the hour.
+    pub fn get_hour(&self, day: NaiveDate, hour: u8) -> String {
+        let texts = self.recurring_on(day)
+            .into_iter()
+            .filter(|recurring| recurring.hour == Some(hour))
+            .map(|recurring| recurring.text);
+        join_entries(self.report(self.get_hour_entry(day, hour)), texts)
     }
 
-    pub fn get_hour(&self, day: NaiveDate, hour: u8) -> String {
+    /// Returns the hour's own entry, without recurring entries. An entry that can't be read isn't
+    /// kept, so that it's read again next time.
+    pub fn get_hour_entry(&self, day: NaiveDate, hour: u8) -> Result<String, io::Error> {
         let mut hours = self.hours.lock().unwrap();
         match hours.entry((day, hour)) {
-            hash_map::Entry::Occupied(oe) => oe.get().to_owned(),
+            hash_map::Entry::Occupied(oe) => Ok(oe.get().to_owned()),
             hash_map::Entry::Vacant(ve) => {
-                let path = self.hour_filename(day, hour);
-                ve.insert(load_entry(&path)).clone()
+                Ok(ve.insert(self.storage.get(Slot::Hour(day, hour))?).clone())
             },
         }
     }
 
-    pub fn edit_hour(&self, day: NaiveDate, hour: u8) {
-        let path = self.hour_filename(day, hour);
-        editor::edit(&path);
+    /// Loads the entries and events of the days from `from` to `to` inclusive with one read of
+    /// each, so that drawing a month or week doesn't have to ask the storage about every hour of
+    /// every day. Does nothing if the days are already loaded, and leaves them to be read one at a
+    /// time if the range can't be read.
+    pub fn prefetch(&self, from: NaiveDate, to: NaiveDate) {
+        let mut days = Vec::new();
+        let mut day = from;
+        while day <= to {
+            days.push(day);
+            day = day.succ();
+        }
+        if days.iter().all(|day| self.days.lock().unwrap().contains_key(day)) {
+            return;
+        }
+
+        let first_event_day = from - one_day() * (MAX_EVENT_DAYS - 1);
+        let (entries, events) = match (self.storage.list_range(from, to),
+                                       self.storage.events_starting_range(first_event_day, to)) {
+            (Ok(entries), Ok(events)) => (entries, events),
+            (Err(e), _) | (_, Err(e)) => return self.report(Err(e)),
+        };
+        let mut entries = entries.into_iter().collect::<HashMap<_, _>>();
+        let mut events_by_day = HashMap::new();
+        for event in events {
+            events_by_day.entry(event.start.date()).or_insert_with(Vec::new).push(event);
+        }
+
+        let mut cached_days = self.days.lock().unwrap();
+        let mut cached_hours = self.hours.lock().unwrap();
+        for &day in &days {
+            cached_days.entry(day).or_insert_with(|| entries.remove(&Slot::Day(day)).unwrap_or_default());
+            for hour in 0..24 {
+                cached_hours.entry((day, hour))
+                            .or_insert_with(|| entries.remove(&Slot::Hour(day, hour)).unwrap_or_default());
+            }
+        }
+        let mut cached_events = self.events.lock().unwrap();
+        let mut day = first_event_day;
+        while day <= to {
+            cached_events.entry(day).or_insert_with(|| events_by_day.remove(&day).unwrap_or_default());
+            day = day.succ();
+        }
+    }
+
+    /// Returns the events that start on `day`.
+    fn events_starting(&self, day: NaiveDate) -> Vec<Event> {
+        let mut events = self.events.lock().unwrap();
+        match events.entry(day) {
+            hash_map::Entry::Occupied(oe) => oe.get().clone(),
+            hash_map::Entry::Vacant(ve) => {
+                ve.insert(self.report(self.storage.events_starting(day))).clone()
+            },
+        }
+    }
+
+    /// Returns every event that is happening at some point during `day`, sorted by start time.
+    pub fn get_events(&self, day: NaiveDate) -> Vec<Event> {
+        let start = day.and_hms(0, 0, 0);
+        let end = start + one_day();
+        let mut events = Vec::new();
+        for days_back in (0..MAX_EVENT_DAYS).rev() {
+            for event in self.events_starting(day - one_day() * days_back) {
+                if !is_long(&event) && (event.overlaps(start, end) || event.start == start) {
+                    events.push(event);
+                }
+            }
+        }
+        events.extend(self.long_events().into_iter().filter(|event| event.overlaps(start, end)));
+        events.sort_by_key(|event| (event.start, event.end));
+        events
+    }
+
+    pub fn get_hour_events(&self, day: NaiveDate, hour: u8) -> Vec<Event> {
+        let hour_start = day.and_hms(hour as u32, 0, 0);
+        self.get_events(day)
+            .into_iter()
+            .filter(|event| !event.all_day)
+            .filter(|event| event.overlaps_hour(day, hour) || event.start == hour_start)
+            .collect()
+    }
+
+    /// Returns the events that last too long to be found by looking back from the days they're
+    /// going on, loading them the first time.
+    fn long_events(&self) -> Vec<Event> {
+        let mut long_events = self.long_events.lock().unwrap();
+        if long_events.is_none() {
+            let loaded = self.report(self.storage.all_events()).into_iter().filter(is_long).collect();
+            *long_events = Some(loaded);
+        }
+        long_events.as_ref().unwrap().clone()
+    }
+
+    /// Forgets the long events if `event` is or was one of them.
+    fn invalidate_long_events(&self, event: &Event) {
+        let mut long_events = self.long_events.lock().unwrap();
+        let was_long = long_events.as_ref().is_some_and(|events| events.iter().any(|e| e.uid == event.uid));
+        if was_long || is_long(event) {
+            *long_events = None;
+        }
+    }
+
+    /// Returns every event in the database.
+    pub fn all_events(&self) -> Vec<Event> {
+        self.report(self.storage.all_events())
+    }
+
+    pub fn put_event(&self, event: &Event) -> Result<(), Error> {
+        let res = self.storage.put_event(event).map_err(Error::Storage);
+        self.events.lock().unwrap().remove(&event.start.date());
+        self.invalidate_long_events(event);
+        res
+    }
+
+    pub fn delete_event(&self, event: &Event) -> Result<(), Error> {
+        let res = self.storage.delete_event(event).map_err(Error::Storage);
+        self.events.lock().unwrap().remove(&event.start.date());
+        self.invalidate_long_events(event);
+        res
+    }
+
+    /// Creates an hour-long event at the given hour and opens it in the editor.
+    pub fn new_event(&self, day: NaiveDate, hour: u8) -> Result<(), Error> {
+        self.edit_event_text(Event::new(day, hour), true)
+    }
+
+    pub fn edit_event(&self, event: &Event) -> Result<(), Error> {
+        self.edit_event_text(event.clone(), false)
+    }
+
+    fn edit_event_text(&self, event: Event, is_new: bool) -> Result<(), Error> {
+        let original = event.to_text();
+        let text = self.edit_text(&event.uid, &original)?;
+        if text.trim().is_empty() {
+            return match is_new {
+                true => Ok(()),
+                false => self.delete_event(&event),
+            };
+        }
+        if text == original {
+            return Ok(());
+        }
+        let edited = Event::parse(&event.uid, &text).map_err(|e| Error::Invalid(format!("invalid event: {}", e)))?;
+        if !is_new && (edited.start.date() != event.start.date() || edited.uid != event.uid) {
+            self.delete_event(&event)?;
+        }
+        self.put_event(&edited)
+    }
+
+    /// Returns every recurring entry, loading them the first time.
+    pub fn all_recurring(&self) -> Vec<Recurring> {
+        let mut recurring = self.recurring.lock().unwrap();
+        if recurring.is_none() {
+            let mut loaded = self.report(self.storage.all_recurring());
+            loaded.sort_by(|a, b| (a.hour, &a.id).cmp(&(b.hour, &b.id)));
+            *recurring = Some(loaded);
+        }
+        recurring.as_ref().unwrap().clone()
+    }
+
+    /// Returns the recurring entries that occur on `day`.
+    pub fn recurring_on(&self, day: NaiveDate) -> Vec<Recurring> {
+        if let Some(occurrences) = self.occurrences.lock().unwrap().get(&day) {
+            return occurrences.clone();
+        }
+        let occurrences = self.all_recurring()
+            .into_iter()
+            .filter(|recurring| recurring.occurs_on(day))
+            .collect::<Vec<_>>();
+        self.occurrences.lock().unwrap().insert(day, occurrences.clone());
+        occurrences
+    }
+
+    /// Returns the recurring entries that occur in the given slot.
+    pub fn get_recurring(&self, slot: Slot) -> Vec<Recurring> {
+        let (day, hour) = match slot {
+            Slot::Day(day) => (day, None),
+            Slot::Hour(day, hour) => (day, Some(hour)),
+        };
+        self.recurring_on(day)
+            .into_iter()
+            .filter(|recurring| recurring.hour == hour)
+            .collect()
+    }
+
+    pub fn put_recurring(&self, recurring: &Recurring) -> Result<(), Error> {
+        let res = self.storage.put_recurring(recurring).map_err(Error::Storage);
+        self.invalidate_recurring();
+        res
+    }
+
+    pub fn delete_recurring(&self, recurring: &Recurring) -> Result<(), Error> {
+        let res = self.storage.delete_recurring(recurring).map_err(Error::Storage);
+        self.invalidate_recurring();
+        res
+    }
+
+    fn invalidate_recurring(&self) {
+        *self.recurring.lock().unwrap() = None;
+        self.occurrences.lock().unwrap().clear();
+    }
+
+    /// Skips a single occurrence of a recurring entry.
+    pub fn skip_occurrence(&self, recurring: &Recurring, day: NaiveDate) -> Result<(), Error> {
+        let mut recurring = recurring.clone();
+        if !recurring.exceptions.contains(&day) {
+            recurring.exceptions.push(day);
+            recurring.exceptions.sort();
+        }
+        self.put_recurring(&recurring)
+    }
+
+    /// Creates a weekly recurring entry starting at the given slot and opens it in the editor.
+    pub fn new_recurring(&self, slot: Slot) -> Result<(), Error> {
+        let (start, hour) = match slot {
+            Slot::Day(day) => (day, None),
+            Slot::Hour(day, hour) => (day, Some(hour)),
+        };
+        let recurring = Recurring::new(event::new_uid(), start, hour);
+        self.edit_recurring_text(recurring, true)
+    }
+
+    pub fn edit_recurring(&self, recurring: &Recurring) -> Result<(), Error> {
+        self.edit_recurring_text(recurring.clone(), false)
+    }
+
+    fn edit_recurring_text(&self, recurring: Recurring, is_new: bool) -> Result<(), Error> {
+        let original = recurring.to_text();
+        let text = self.edit_text(&recurring.id, &original)?;
+        if text.trim().is_empty() {
+            return match is_new {
+                true => Ok(()),
+                false => self.delete_recurring(&recurring),
+            };
+        }
+        if text == original {
+            return Ok(());
+        }
+        let edited = Recurring::parse(&recurring.id, &text).map_err(|e| {
+            Error::Invalid(format!("invalid recurring entry: {}", e))
+        })?;
+        self.put_recurring(&edited)
+    }
+
+    /// Writes an entry to storage, deleting it if it's empty.
+    fn write(&self, slot: Slot, text: &str) -> Result<(), Error> {
+        match text.is_empty() {
+            true => self.storage.delete(slot),
+            false => self.storage.put(slot, text),
+        }.map_err(Error::Storage)
+    }
+
+    pub fn set_day(&self, day: NaiveDate, text: &str) -> Result<(), Error> {
+        self.write(Slot::Day(day), text)?;
+        let mut days = self.days.lock().unwrap();
+        days.insert(day, text.to_owned());
+        Ok(())
+    }
+
+    pub fn set_hour(&self, day: NaiveDate, hour: u8, text: &str) -> Result<(), Error> {
+        self.write(Slot::Hour(day, hour), text)?;
         let mut hours = self.hours.lock().unwrap();
-        hours.insert((day, hour), load_entry(&path));
+        hours.insert((day, hour), text.to_owned());
+        Ok(())
+    }
+
+    /// Returns the slot's own text, without recurring entries, for editing. Fails if it can't be
+    /// read, so that what's in storage isn't replaced by an edit of an empty entry.
+    pub fn get(&self, slot: Slot) -> Result<String, Error> {
+        match slot {
+            Slot::Day(day) => self.get_day_entry(day),
+            Slot::Hour(day, hour) => self.get_hour_entry(day, hour),
+        }.map_err(Error::Storage)
+    }
+
+    pub fn set(&self, slot: Slot, text: &str) -> Result<(), Error> {
+        match slot {
+            Slot::Day(day) => self.set_day(day, text),
+            Slot::Hour(day, hour) => self.set_hour(day, hour, text),
+        }
+    }
+
+    /// Adds a line to the end of an entry.
+    pub fn append(&self, slot: Slot, line: &str) -> Result<(), Error> {
+        let text = self.get(slot)?;
+        let text = match text.trim_end() {
+            "" => String::from(line),
+            existing => format!("{}\n{}", existing, line),
+        };
+        self.set(slot, &text)
+    }
+
+    /// Opens an entry in the external editor.
+    pub fn edit(&self, slot: Slot) -> Result<(), Error> {
+        let name = match slot {
+            Slot::Day(day) => day.format("%Y-%m-%d").to_string(),
+            Slot::Hour(day, hour) => format!("{}-{:02}", day.format("%Y-%m-%d"), hour),
+        };
+        let original = self.get(slot)?;
+        let text = self.edit_text(&name, &original)?;
+        if text != original {
+            self.set(slot, &text)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the editor on a temporary file holding `text` and returns what was saved, since the
+    /// storage isn't necessarily made of files the editor could open.
+    fn edit_text(&self, name: &str, text: &str) -> Result<String, Error> {
+        let name = name.chars().map(|c| match c.is_alphanumeric() {
+            true => c,
+            false => '-',
+        }).collect::<String>();
+        let dir = private_temp_dir()?;
+        let path = dir.join(format!("{}.txt", name));
+        let written = private_file(OpenOptions::new().write(true).create_new(true))
+            .open(&path)
+            .and_then(|mut f| f.write_all(text.as_bytes()));
+        if let Err(e) = written {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(Error::from(e));
+        }
+        let res = self.editor.edit(&path);
+        let mut edited = String::new();
+        let read = File::open(&path).and_then(|mut f| f.read_to_string(&mut edited));
+        let _ = fs::remove_dir_all(&dir);
+        res?;
+        read?;
+        Ok(edited)
+    }
+
+    /// Finds every entry, event and recurring entry containing `query`, ignoring case. Results
+    /// are in date order.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query = query.to_lowercase();
+        let matching_line = |text: &str| {
+            text.lines()
+                .find(|line| line.to_lowercase().contains(&query))
+                .map(|line| String::from(line.trim()))
+        };
+        let mut results = Vec::new();
+        for (slot, text) in self.report(self.storage.list_range(date::MIN, date::MAX)) {
+            if let Some(line) = matching_line(&text) {
+                results.push(SearchResult { slot, line });
+            }
+        }
+        for event in self.all_events() {
+            let text = format!("{}\n{}\n{}", event.title,
+                               event.location.clone().unwrap_or_default(),
+                               event.description.clone().unwrap_or_default());
+            if let Some(line) = matching_line(&text) {
+                let slot = match event.all_day {
+                    true => Slot::Day(event.start.date()),
+                    false => Slot::Hour(event.start.date(), event.start.hour() as u8),
+                };
+                let line = match line == event.title {
+                    true => line,
+                    false => format!("{}: {}", event.title, line),
+                };
+                results.push(SearchResult { slot, line });
+            }
+        }
+        for recurring in self.all_recurring() {
+            if let Some(line) = matching_line(&recurring.text) {
+                let slot = match recurring.hour {
+                    Some(hour) => Slot::Hour(recurring.start, hour),
+                    None => Slot::Day(recurring.start),
+                };
+                results.push(SearchResult { slot, line: format!("{} ({})", line, recurring.rule) });
+            }
+        }
+        results.sort_by_key(|result| match result.slot {
+            Slot::Day(day) => (day, None),
+            Slot::Hour(day, hour) => (day, Some(hour)),
+        });
+        results
+    }
+
+    /// Returns the value of a read from storage, or the default if it failed. The error is kept
+    /// for `take_error`, since a failed read shouldn't stop the calendar being drawn.
+    fn report<T: Default>(&self, res: Result<T, io::Error>) -> T {
+        match res {
+            Ok(value) => value,
+            Err(e) => {
+                let mut error = self.error.lock().unwrap();
+                if error.is_none() {
+                    *error = Some(Error::Storage(e));
+                }
+                T::default()
+            },
+        }
+    }
+
+    /// Returns the first error hit while reading from storage since this was last called, or
+    /// else the first entry that was skipped because it isn't valid.
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.lock().unwrap().take().or_else(|| self.storage.take_invalid().map(Error::Storage))
+    }
+
+    /// Forgets everything that has been loaded so that it's read from storage again, eg. to pick
+    /// up changes made by another process.
+    pub fn reload(&self) {
+        self.days.lock().unwrap().clear();
+        self.hours.lock().unwrap().clear();
+        self.events.lock().unwrap().clear();
+        *self.long_events.lock().unwrap() = None;
+        self.invalidate_recurring();
+    }
+
+    /// Forgets what has been loaded for whatever `change` covers, so that it's read from storage
+    /// again.
+    pub fn invalidate(&self, change: Change) {
+        match change {
+            Change::Day(day) => {
+                self.days.lock().unwrap().remove(&day);
+                let mut hours = self.hours.lock().unwrap();
+                for hour in 0..24 {
+                    hours.remove(&(day, hour));
+                }
+                self.events.lock().unwrap().remove(&day);
+                // The day may have a long event that was changed.
+                *self.long_events.lock().unwrap() = None;
+            },
+            Change::Recurring => self.invalidate_recurring(),
+            Change::All => self.reload(),
+        }
+    }
+
+    /// Calls `changed` whenever the storage is changed from outside. The caller should pass the
+    /// change on to `invalidate`. Watching stops when the returned watcher is dropped.
+    pub fn watch<F: Fn(Change) + Send + 'static>(&self, changed: F) -> Result<Option<RecommendedWatcher>, Error> {
+        self.storage.watch(Box::new(changed)).map_err(Error::Storage)
+    }
+
+    /// Copies everything in `from` into this database's storage. Returns how many entries, events
+    /// and recurring entries were copied.
+    pub fn import_from(&self, from: &dyn Storage) -> Result<(usize, usize, usize), Error> {
+        let res = storage::copy(from, &*self.storage).map_err(Error::Storage);
+        self.reload();
+        match from.take_invalid() {
+            Some(e) if res.is_ok() => Err(Error::Storage(e)),
+            _ => res,
+        }
+    }
+
+    /// The number of non-empty entries and events on `day`, counting the day's note as one.
+    pub fn count_entries(&self, day: NaiveDate) -> usize {
+        let note = !self.get_day(day).trim().is_empty() as usize;
+        let hours = (0..24).filter(|&hour| !self.get_hour(day, hour).trim().is_empty()).count();
+        note + hours + self.get_events(day).len()
+    }
+
+    pub fn has_entries(&self, day: NaiveDate) -> bool {
+        !self.get_day(day).is_empty()
+            || (0..24).any(|hour| !self.get_hour(day, hour).is_empty())
+            || !self.get_events(day).is_empty()
     }
 
 }
 
-fn load_entry(path: &Path) -> String {
-    if path.exists() {
-        let mut s = String::new();
-        let mut f = File::open(&path).unwrap();
-        f.read_to_string(&mut s).unwrap();
-        s
-    } else {
-        String::new()
+/// Makes a new directory that only the user can get into, in `$XDG_RUNTIME_DIR` or else the
+/// temporary directory, for entries to be edited in. Creating it fails rather than reusing a
+/// directory someone else made first, so the files in it can't be swapped or read by other users.
+fn private_temp_dir() -> Result<PathBuf, io::Error> {
+    let base = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(env::temp_dir);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    for attempt in 0..100 {
+        let dir = base.join(format!("callus-{}-{}-{}", process::id(), nanos, attempt));
+        match private_dir(&mut DirBuilder::new()).create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
     }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "couldn't make a temporary directory to edit in"))
 }
 
+/// Makes new directories only the user can get into.
+#[cfg(unix)]
+fn private_dir(builder: &mut DirBuilder) -> &mut DirBuilder {
+    builder.mode(0o700)
+}
+
+/// Elsewhere the temporary directory belongs to the user already, eg. `%TEMP%` on Windows.
+#[cfg(not(unix))]
+fn private_dir(builder: &mut DirBuilder) -> &mut DirBuilder {
+    builder
+}
+
+/// Makes new files only the user can read.
+#[cfg(unix)]
+fn private_file(options: &mut OpenOptions) -> &mut OpenOptions {
+    options.mode(0o600)
+}
+
+#[cfg(not(unix))]
+fn private_file(options: &mut OpenOptions) -> &mut OpenOptions {
+    options
+}
+
+/// Whether `event` may still be going on after the last day `get_events` looks back from, in which
+/// case it's kept with the long events.
+fn is_long(event: &Event) -> bool {
+    event.end > event.start.date().and_hms(0, 0, 0) + one_day() * MAX_EVENT_DAYS
+}
+
+/// Appends the text of recurring entries to an entry, each starting on a new line.
+fn join_entries<I: Iterator<Item = String>>(entry: String, texts: I) -> String {
+    let mut joined = entry;
+    for text in texts {
+        let text = text.trim_end();
+        if text.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() && !joined.ends_with('\n') {
+            joined.push('\n');
+        }
+        joined.push_str(text);
+        joined.push('\n');
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use ::storage::memory::MemoryStorage;
+
+    fn database() -> Database {
+        Database::new(Box::new(MemoryStorage::new()), Editor::new(None))
+    }
+
+    fn event(uid: &str, start: NaiveDate, days: i64) -> Event {
+        Event {
+            uid: String::from(uid),
+            title: String::from(uid),
+            start: start.and_hms(9, 0, 0),
+            end: start.and_hms(9, 0, 0) + Duration::days(days),
+            all_day: false,
+            location: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn set_and_get() {
+        let db = database();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Day(day), "note\n").unwrap();
+        db.set(Slot::Hour(day, 15), "meeting\n").unwrap();
+        assert_eq!(db.get(Slot::Day(day)).unwrap(), "note\n");
+        assert_eq!(db.get(Slot::Hour(day, 15)).unwrap(), "meeting\n");
+        assert_eq!(db.get(Slot::Hour(day, 16)).unwrap(), "");
+        assert_eq!(db.storage.get(Slot::Hour(day, 15)).unwrap(), "meeting\n");
+
+        db.set(Slot::Hour(day, 15), "").unwrap();
+        assert_eq!(db.get(Slot::Hour(day, 15)).unwrap(), "");
+        assert_eq!(db.storage.get(Slot::Hour(day, 15)).unwrap(), "");
+    }
+
+    #[test]
+    fn append() {
+        let db = database();
+        let slot = Slot::Day(NaiveDate::from_ymd(2017, 5, 31));
+        db.append(slot, "first").unwrap();
+        db.append(slot, "second").unwrap();
+        assert_eq!(db.get(slot).unwrap(), "first\nsecond");
+    }
+
+    #[test]
+    fn list_range() {
+        let db = database();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Day(day - one_day()), "before").unwrap();
+        db.set(Slot::Day(day), "note").unwrap();
+        db.set(Slot::Hour(day + one_day(), 9), "hour").unwrap();
+        db.set(Slot::Day(day + one_day() * 2), "after").unwrap();
+
+        let mut listed = db.storage.list_range(day, day + one_day()).unwrap();
+        listed.sort_by_key(|&(slot, _)| match slot {
+            Slot::Day(day) => (day, None),
+            Slot::Hour(day, hour) => (day, Some(hour)),
+        });
+        assert_eq!(listed, vec![
+            (Slot::Day(day), String::from("note")),
+            (Slot::Hour(day + one_day(), 9), String::from("hour")),
+        ]);
+    }
+
+    #[test]
+    fn long_events() {
+        let db = database();
+        let day = NaiveDate::from_ymd(2017, 5, 1);
+        db.put_event(&event("short", day, 1)).unwrap();
+        db.put_event(&event("long", day, 30)).unwrap();
+        let uids = |day| {
+            let mut uids = db.get_events(day).into_iter().map(|event| event.uid).collect::<Vec<_>>();
+            uids.sort();
+            uids
+        };
+        assert_eq!(uids(day + one_day()), vec!["long", "short"]);
+        assert_eq!(uids(day + one_day() * 20), vec!["long"]);
+        assert_eq!(uids(day + one_day() * 31), Vec::<String>::new());
+    }
+
+    #[test]
+    fn prefetch_loads_entries() {
+        let storage = MemoryStorage::new();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        storage.put(Slot::Hour(day, 8), "breakfast").unwrap();
+        let db = Database::new(Box::new(storage), Editor::new(None));
+        db.prefetch(day, day);
+        assert_eq!(db.hours.lock().unwrap().get(&(day, 8)).map(|s| &s[..]), Some("breakfast"));
+        assert_eq!(db.get_hour(day, 8), "breakfast");
+        assert_eq!(db.get_day(day), "");
+    }
+
+    #[test]
+    fn search() {
+        let db = database();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Hour(day, 15), "Project meeting\nBring the PROJECT plan\n").unwrap();
+        db.set(Slot::Day(day - one_day()), "Nothing here\nproject notes").unwrap();
+        db.set(Slot::Day(day + one_day()), "Unrelated").unwrap();
+        let mut dentist = event("dentist", day, 0);
+        dentist.title = String::from("Dentist");
+        dentist.location = Some(String::from("Project Street"));
+        db.put_event(&dentist).unwrap();
+        let mut recurring = Recurring::new(String::from("weekly"), day - one_day() * 7, Some(10));
+        recurring.text = String::from("Weekly\nproject review");
+        db.put_recurring(&recurring).unwrap();
+
+        let results = db.search("PROJECT").into_iter().map(|result| (result.slot, result.line)).collect::<Vec<_>>();
+        assert_eq!(results, vec![
+            (Slot::Hour(day - one_day() * 7, 10), String::from("project review (FREQ=WEEKLY)")),
+            (Slot::Day(day - one_day()), String::from("project notes")),
+            (Slot::Hour(day, 9), String::from("Dentist: Project Street")),
+            (Slot::Hour(day, 15), String::from("Project meeting")),
+        ]);
+        assert!(db.search("dentist").iter().any(|result| result.line == "Dentist"));
+        assert!(db.search("missing").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_temp_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = private_temp_dir().unwrap();
+        let other = private_temp_dir().unwrap();
+        assert_ne!(dir, other);
+        assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        let path = dir.join("entry.txt");
+        private_file(OpenOptions::new().write(true).create_new(true)).open(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(private_file(OpenOptions::new().write(true).create_new(true)).open(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other).unwrap();
+    }
+}
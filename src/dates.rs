@@ -0,0 +1,195 @@
+use chrono::naive::date::NaiveDate;
+use chrono::{Datelike, Duration};
+
+use std::convert::TryFrom;
+
+/// Parses a date typed by the user, relative to `today`. Understands:
+///
+/// - `2025-03-14`
+/// - `today`, `tomorrow` and `yesterday`
+/// - offsets such as `+3w`, `-10d`, `+1m` or `+2y`, where a bare number is days, and `in 3 weeks`
+/// - `friday` for the next Friday from today, or today if it is one; `next friday` for the one
+///   after today and `last friday` for the one before
+/// - `next week`, `last month`, `next year` and so on
+/// - `dec 25` or `25 december` for the next 25th of December, or `dec 25 2027` for a given year
+pub fn parse(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let s = s.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&s, "%Y-%m-%d") {
+        return Some(date);
+    }
+    match &s[..] {
+        "today" | "now" => return Some(today),
+        "tomorrow" => return today.succ_opt(),
+        "yesterday" => return today.pred_opt(),
+        _ => (),
+    }
+    if s.starts_with('+') || s.starts_with('-') {
+        return parse_offset(&s, today);
+    }
+
+    let words = s.split_whitespace().collect::<Vec<_>>();
+    match words[..] {
+        [direction @ "next", unit] | [direction @ "last", unit] => {
+            let sign = if direction == "next" { 1 } else { -1 };
+            match unit {
+                "day" => today.checked_add_signed(Duration::days(sign)),
+                "week" => today.checked_add_signed(Duration::weeks(sign)),
+                "month" => add_months(today, sign as i32),
+                "year" => add_months(today, 12 * sign as i32),
+                _ => {
+                    let weekday = weekday_from_name(unit)? as i64;
+                    let today_weekday = today.weekday().num_days_from_monday() as i64;
+                    let days = match direction {
+                        "next" => (weekday - today_weekday + 6).rem_euclid(7) + 1,
+                        _ => -((today_weekday - weekday + 6).rem_euclid(7) + 1),
+                    };
+                    today.checked_add_signed(Duration::days(days))
+                },
+            }
+        },
+        ["in", n, unit] => parse_offset(&format!("+{}{}", n, unit), today),
+        [word] if weekday_from_name(word).is_some() => {
+            let weekday = weekday_from_name(word)?;
+            let offset = (7 + weekday - today.weekday().num_days_from_monday()) % 7;
+            today.checked_add_signed(Duration::days(offset as i64))
+        },
+        [a, b] => {
+            let (month, day) = month_and_day(a, b)?;
+            next_date(today, month, day)
+        },
+        [a, b, year] => {
+            let (month, day) = month_and_day(a, b)?;
+            NaiveDate::from_ymd_opt(year.parse().ok()?, month, day)
+        },
+        _ => None,
+    }
+}
+
+/// `date` moved by a number of months, keeping the day of the month where possible and otherwise
+/// using the last day of the month.
+pub fn add_months(date: NaiveDate, months: i32) -> Option<NaiveDate> {
+    let month0 = date.year().checked_mul(12)?.checked_add(date.month0() as i32)?.checked_add(months)?;
+    let (year, month) = (month0.div_euclid(12), month0.rem_euclid(12) as u32 + 1);
+    (1..date.day() + 1).rev().filter_map(|day| NaiveDate::from_ymd_opt(year, month, day)).next()
+}
+
+/// Parses an offset from `today` such as `+3w`. Offsets too big for a date give `None`.
+fn parse_offset(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (number, unit) = match s.find(|c: char| c.is_alphabetic()) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, "d"),
+    };
+    // Any number of days or weeks that fits in an `i32` fits in a `Duration`, which panics
+    // rather than overflowing.
+    let n = i32::try_from(number.parse::<i64>().ok()?).ok()?;
+    match unit {
+        "d" | "day" | "days" => today.checked_add_signed(Duration::days(n as i64)),
+        "w" | "week" | "weeks" => today.checked_add_signed(Duration::weeks(n as i64)),
+        "m" | "month" | "months" => add_months(today, n),
+        "y" | "year" | "years" => add_months(today, n.checked_mul(12)?),
+        _ => None,
+    }
+}
+
+/// The month and day from two words, in either order, eg. `dec 25` or `25 december`.
+fn month_and_day(a: &str, b: &str) -> Option<(u32, u32)> {
+    match (month_from_name(a), month_from_name(b)) {
+        (Some(month), None) => Some((month, b.parse().ok()?)),
+        (None, Some(month)) => Some((month, a.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// The next `month` and `day` on or after `today`.
+fn next_date(today: NaiveDate, month: u32, day: u32) -> Option<NaiveDate> {
+    match NaiveDate::from_ymd_opt(today.year(), month, day) {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+/// Reads a month's name or any abbreviation of it at least three letters long, eg. `dec`.
+fn month_from_name(name: &str) -> Option<u32> {
+    const NAMES: [&str; 12] = ["january", "february", "march", "april", "may", "june", "july",
+                               "august", "september", "october", "november", "december"];
+    if name.len() < 3 {
+        return None;
+    }
+    NAMES.iter().position(|month| month.starts_with(name)).map(|i| i as u32 + 1)
+}
+
+pub const WEEKDAY_NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// Reads a weekday's name or any abbreviation of it at least three letters long as its number of
+/// days from Monday.
+pub fn weekday_from_name(name: &str) -> Option<u32> {
+    if name.len() < 3 {
+        return None;
+    }
+    WEEKDAY_NAMES.iter().position(|day| day.starts_with(name)).map(|i| i as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    /// A Wednesday.
+    fn today() -> NaiveDate {
+        date(2017, 5, 31)
+    }
+
+    #[test]
+    fn absolute_and_named() {
+        assert_eq!(parse("2025-03-14", today()), Some(date(2025, 3, 14)));
+        assert_eq!(parse(" Today ", today()), Some(today()));
+        assert_eq!(parse("tomorrow", today()), Some(date(2017, 6, 1)));
+        assert_eq!(parse("yesterday", today()), Some(date(2017, 5, 30)));
+        assert_eq!(parse("dec 25", today()), Some(date(2017, 12, 25)));
+        assert_eq!(parse("25 December", today()), Some(date(2017, 12, 25)));
+        assert_eq!(parse("may 1", today()), Some(date(2018, 5, 1)));
+        assert_eq!(parse("dec 25 2027", today()), Some(date(2027, 12, 25)));
+        assert_eq!(parse("feb 30", today()), None);
+        assert_eq!(parse("soon", today()), None);
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(parse("wednesday", today()), Some(today()));
+        assert_eq!(parse("fri", today()), Some(date(2017, 6, 2)));
+        assert_eq!(parse("monday", today()), Some(date(2017, 6, 5)));
+        assert_eq!(parse("next wed", today()), Some(date(2017, 6, 7)));
+        assert_eq!(parse("last wednesday", today()), Some(date(2017, 5, 24)));
+        assert_eq!(parse("last tuesday", today()), Some(date(2017, 5, 30)));
+        assert_eq!(parse("mo", today()), None);
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse("+3", today()), Some(date(2017, 6, 3)));
+        assert_eq!(parse("-10d", today()), Some(date(2017, 5, 21)));
+        assert_eq!(parse("+2w", today()), Some(date(2017, 6, 14)));
+        assert_eq!(parse("+1m", today()), Some(date(2017, 6, 30)));
+        assert_eq!(parse("-3m", today()), Some(date(2017, 2, 28)));
+        assert_eq!(parse("+2y", today()), Some(date(2019, 5, 31)));
+        assert_eq!(parse("in 3 weeks", today()), Some(date(2017, 6, 21)));
+        assert_eq!(parse("next month", today()), Some(date(2017, 6, 30)));
+        assert_eq!(parse("last year", today()), Some(date(2016, 5, 31)));
+        assert_eq!(parse("+3x", today()), None);
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(parse("+99999999999999999999d", today()), None);
+        assert_eq!(parse("+2147483647d", today()), None);
+        assert_eq!(parse("+2147483647w", today()), None);
+        assert_eq!(parse("+2147483647m", today()), None);
+        assert_eq!(parse("-2147483648m", today()), None);
+        assert_eq!(parse("+2147483647y", today()), None);
+        assert_eq!(parse("in 400000000 years", today()), None);
+        assert_eq!(add_months(date(2017, 5, 31), i32::MAX), None);
+    }
+}
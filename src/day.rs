@@ -0,0 +1,261 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::{border, Widget, Block};
+use tui::style::Style;
+
+use chrono::naive::date::NaiveDate;
+use chrono::offset::local::Local;
+use chrono::{Datelike, Timelike};
+
+use std::cmp::max;
+use std::sync::Arc;
+use std::cell::Cell;
+
+use ::hour_name;
+use ::{HitAreas, one_day};
+use ::locale;
+use ::database::{Database, Slot};
+use ::theme::{self, Theme};
+
+/// Shows a single day as the day note followed by all 24 hour entries. Slot 0 is the day note and
+/// slot `n` is hour `n - 1`.
+pub struct DayView {
+    selected_date: NaiveDate,
+    selected_hour: Option<u8>,
+    top_slot: Cell<u8>,
+    db: Arc<Database>,
+    theme: Theme,
+    hit_areas: HitAreas,
+}
+
+impl DayView {
+    pub fn new(db: Arc<Database>, theme: Theme, date: NaiveDate, hour: u8) -> DayView {
+        DayView {
+            selected_date: date,
+            selected_hour: Some(hour),
+            top_slot: Cell::new(0),
+            db,
+            theme,
+            hit_areas: HitAreas::default(),
+        }
+    }
+
+    pub fn set_date(&mut self, date: NaiveDate, hour: u8) {
+        self.selected_date = date;
+        self.selected_hour = Some(hour);
+    }
+
+    pub fn set_slot(&mut self, slot: Slot) {
+        match slot {
+            Slot::Day(date) => {
+                self.selected_date = date;
+                self.selected_hour = None;
+            },
+            Slot::Hour(date, hour) => self.set_date(date, hour),
+        }
+    }
+
+    pub fn get_date(&self) -> NaiveDate {
+        self.selected_date
+    }
+
+    pub fn get_hour(&self) -> Option<u8> {
+        self.selected_hour
+    }
+
+    pub fn up(&mut self) -> bool {
+        self.selected_hour = match self.selected_hour {
+            None => {
+                self.selected_date = self.selected_date - one_day();
+                Some(23)
+            },
+            Some(0) => None,
+            Some(hour) => Some(hour - 1),
+        };
+        false
+    }
+
+    pub fn down(&mut self) -> bool {
+        self.selected_hour = match self.selected_hour {
+            None => Some(0),
+            Some(23) => {
+                self.selected_date = self.selected_date + one_day();
+                None
+            },
+            Some(hour) => Some(hour + 1),
+        };
+        false
+    }
+
+    pub fn left(&mut self) -> bool {
+        self.selected_date = self.selected_date - one_day();
+        false
+    }
+
+    pub fn right(&mut self) -> bool {
+        self.selected_date = self.selected_date + one_day();
+        false
+    }
+
+    pub fn selected_slot(&self) -> Slot {
+        match self.selected_hour {
+            None => Slot::Day(self.selected_date),
+            Some(hour) => Slot::Hour(self.selected_date, hour),
+        }
+    }
+
+    /// The day note or hour drawn at `x`, `y` as of the last draw.
+    pub fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        self.hit_areas.slot_at(x, y)
+    }
+
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer) {
+        self.hit_areas.clear();
+        if area.width < 3 || area.height < 3 {
+            return;
+        }
+
+        let date = self.selected_date;
+        let title = locale::get().date_title(date);
+        let title_x = area.x + area.width.saturating_sub(title.chars().count() as u16) / 2;
+        buffer.set_stringn(title_x, area.y, &title, area.width as usize, &self.theme.title.style());
+
+        let text_width = area.width as usize - 2;
+        let slots = (0..25u8).map(|slot| {
+            let summary = match slot {
+                0 => {
+                    let mut summary = String::new();
+                    for event in self.db.get_events(date).into_iter().filter(|event| event.all_day) {
+                        summary.push_str(&event.title);
+                        summary.push('\n');
+                    }
+                    summary.push_str(&self.db.get_day(date));
+                    summary
+                },
+                _ => {
+                    let hour = slot - 1;
+                    let mut summary = String::new();
+                    for event in self.db.get_hour_events(date, hour) {
+                        summary.push_str(&event.hour_label(date, hour));
+                        if event.start.hour() as u8 == hour && event.start.date() == date {
+                            if let Some(ref location) = event.location {
+                                summary.push_str(&format!(" @ {}", location));
+                            }
+                        }
+                        summary.push('\n');
+                    }
+                    summary.push_str(&self.db.get_hour(date, hour));
+                    summary
+                },
+            };
+            wrap_lines(&summary, text_width)
+        }).collect::<Vec<_>>();
+        let slot_height = |slot: u8| 1 + max(1, slots[slot as usize].len()) as u16;
+
+        // Scroll so that the selected slot is fully on screen.
+        let top = area.y + 2;
+        let height = area.bottom() - top;
+        let selected_slot = match self.selected_hour {
+            None => 0,
+            Some(hour) => hour + 1,
+        };
+        if selected_slot < self.top_slot.get() {
+            self.top_slot.set(selected_slot);
+        }
+        while self.top_slot.get() < selected_slot
+            && (self.top_slot.get()..=selected_slot).map(&slot_height).sum::<u16>() > height
+        {
+            self.top_slot.set(self.top_slot.get() + 1);
+        }
+
+        let today = Local::now().naive_local().date();
+        let now = Local::now().naive_local().hour() as u8;
+        let mut y = top;
+        for slot in self.top_slot.get()..25 {
+            if y >= area.bottom() {
+                break;
+            }
+            let selected = slot == selected_slot;
+            let is_now = slot > 0 && date == today && slot - 1 == now;
+            let rect = Rect {
+                x: area.x,
+                y,
+                width: area.width,
+                height: slot_height(slot).min(area.bottom() - y),
+            };
+            self.hit_areas.add(rect, match slot {
+                0 => Slot::Day(date),
+                _ => Slot::Hour(date, slot - 1),
+            });
+
+            let title_style = {
+                let mut title_style = Style::default();
+                if selected {
+                    title_style = self.theme.selected.apply(title_style);
+                }
+                if is_now {
+                    title_style = self.theme.today.apply(title_style);
+                }
+                title_style
+            };
+
+            let slot_title = match slot {
+                0 => String::from(locale::get().day_name(date.weekday())),
+                _ => hour_name(slot - 1),
+            };
+            let block = Block::default()
+                    .title(&slot_title)
+                    .borders(border::TOP)
+                    .title_style(title_style)
+                    .border_style(title_style);
+            block.draw(&rect, buffer);
+
+            let mut style = self.theme.entry.style();
+            if selected {
+                theme::fill(&block.inner(&rect), buffer, &self.theme.selected.style());
+                style = self.theme.selected.apply(style);
+            }
+            for (line, sy) in slots[slot as usize].iter().zip((rect.y + 1)..rect.bottom()) {
+                buffer.set_stringn(rect.x + 1, sy, line, text_width, &style);
+            }
+            y += rect.height;
+        }
+    }
+}
+
+/// Splits `text` into lines no wider than `width` characters, breaking at spaces where possible.
+fn wrap_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let mut word = word;
+            loop {
+                let current_len = current.chars().count();
+                let word_len = word.chars().count();
+                let space = if current_len == 0 { 0 } else { 1 };
+                if current_len + space + word_len <= width {
+                    if space == 1 {
+                        current.push(' ');
+                    }
+                    current.push_str(word);
+                    break;
+                }
+                if current_len > 0 {
+                    lines.push(current);
+                    current = String::new();
+                    continue;
+                }
+                let split = word.char_indices().nth(width).map(|(i, _)| i).unwrap_or(word.len());
+                lines.push(word[..split].to_owned());
+                word = &word[split..];
+                if word.is_empty() {
+                    break;
+                }
+            }
+        }
+        lines.push(current);
+    }
+    lines
+}
+
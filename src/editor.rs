@@ -1,14 +1,147 @@
+use std::env;
 use std::process::Command;
 use std::path::Path;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
 use termion::screen::ToAlternateScreen;
 
-pub fn edit(path: &Path) {
-    if let Some(dir) = path.parent() {
-        fs::create_dir_all(dir).unwrap();
+use ::error::Error;
+
+/// Editors to try, in order, when neither the config file nor the environment names one.
+const FALLBACK_EDITORS: [&str; 3] = ["vim", "vi", "nano"];
+
+/// Turn the mouse reporting that `termion::input::MouseTerminal` enables off and back on, so that
+/// clicks in the editor aren't read as escape sequences.
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+
+/// The command used to edit entries, split into words.
+pub struct Editor {
+    command: Option<Vec<String>>,
+}
+
+impl Editor {
+    /// Picks the editor from the config setting, then `$VISUAL`, then `$EDITOR`. If none of those
+    /// are set the fallback editors are tried when editing. Empty settings count as unset.
+    pub fn new(configured: Option<&str>) -> Editor {
+        let words = |s: &str| Some(split_command(s)).filter(|words| !words.is_empty());
+        let command = configured
+            .and_then(words)
+            .or_else(|| env::var("VISUAL").ok().and_then(|s| words(&s)))
+            .or_else(|| env::var("EDITOR").ok().and_then(|s| words(&s)));
+        Editor {
+            command,
+        }
     }
 
-    Command::new("vim").arg(path).spawn().unwrap().wait().unwrap();
-    print!("{}", ToAlternateScreen);
+    pub fn edit(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let line = last_line(path);
+        print!("{}", MOUSE_OFF);
+        let _ = io::stdout().flush();
+        let res = match self.command {
+            Some(ref command) => run(command, path, line),
+            None => {
+                let mut res = Err(RunError::NotFound);
+                for editor in FALLBACK_EDITORS.iter() {
+                    res = run(&[String::from(*editor)], path, line);
+                    match res {
+                        Err(RunError::NotFound) => continue,
+                        _ => break,
+                    }
+                }
+                res
+            },
+        };
+        print!("{}{}", ToAlternateScreen, MOUSE_ON);
+        res.map_err(|e| match e {
+            RunError::NotFound => Error::Editor(match self.command {
+                Some(ref command) => format!("editor `{}` not found", command[0]),
+                None => String::from("no editor found, set $EDITOR"),
+            }),
+            RunError::Failed(s) => Error::Editor(s),
+        })
+    }
+}
+
+/// Why running the editor failed. Not finding the program is told apart so that the next
+/// fallback editor can be tried.
+enum RunError {
+    NotFound,
+    Failed(String),
+}
+
+fn run(command: &[String], path: &Path, line: usize) -> Result<(), RunError> {
+    let path_str = path.to_string_lossy();
+    let line_str = line.to_string();
+    let mut args = command[1..].iter().map(|arg| {
+        arg.replace("{file}", &path_str).replace("{line}", &line_str)
+    }).collect::<Vec<_>>();
+    if !command.iter().any(|arg| arg.contains("{file}")) {
+        args.push(path_str.into_owned());
+    }
+
+    let program = &command[0];
+    let status = Command::new(program).args(&args).status().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => RunError::NotFound,
+        _ => RunError::Failed(format!("failed to run editor `{}`: {}", program, e)),
+    })?;
+    if !status.success() {
+        return Err(RunError::Failed(format!("editor `{}` exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+/// The line number of the last line in `path`, so that editors can open with the cursor at the
+/// end of the entry.
+fn last_line(path: &Path) -> usize {
+    match File::open(path) {
+        Ok(f) => BufReader::new(f).lines().count().max(1),
+        Err(..) => 1,
+    }
+}
+
+/// Splits a command line into words, honouring single quotes, double quotes and backslash
+/// escapes.
+pub fn split_command(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+                in_word = true;
+            },
+            (Some(..), c) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(word);
+                    word = String::new();
+                    in_word = false;
+                }
+            },
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
 }
 
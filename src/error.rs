@@ -0,0 +1,56 @@
+use xdg;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong in callus. While the calendar is open these are shown on its
+/// bottom line rather than ending the program.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Reading or writing entries, events or recurring entries failed.
+    Storage(io::Error),
+    /// The editor couldn't be started or exited unsuccessfully.
+    Editor(String),
+    /// The config file couldn't be read.
+    Config(String),
+    /// An edited entry or an imported file isn't valid.
+    Invalid(String),
+    /// A command was given the wrong arguments. Holds the command's usage.
+    Usage(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Storage(ref e) => write!(f, "storage error: {}", e),
+            Error::Editor(ref s) => write!(f, "{}", s),
+            Error::Config(ref s) => write!(f, "invalid config: {}", s),
+            Error::Invalid(ref s) => write!(f, "{}", s),
+            Error::Usage(ref s) => write!(f, "usage: {}", s),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) | Error::Storage(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<xdg::BaseDirectoriesError> for Error {
+    fn from(e: xdg::BaseDirectoriesError) -> Error {
+        Error::Io(e.into())
+    }
+}
@@ -0,0 +1,232 @@
+use chrono::naive::date::NaiveDate;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::offset::local::Local;
+use chrono::{Duration, Timelike};
+
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Something happening between two points in time. Unlike hour entries, events can start and end
+/// at any minute and several of them can overlap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub title: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// All-day events run from midnight to midnight and are shown with the day's note rather
+    /// than in the hour boxes.
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Event {
+    /// Creates an hour-long event starting at the given hour, with a freshly generated uid.
+    pub fn new(day: NaiveDate, hour: u8) -> Event {
+        let start = day.and_hms(hour as u32, 0, 0);
+        Event {
+            uid: new_uid(),
+            title: String::new(),
+            start,
+            end: start + Duration::hours(1),
+            all_day: false,
+            location: None,
+            description: None,
+        }
+    }
+
+    pub fn overlaps(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        self.start < end && self.end > start
+    }
+
+    pub fn overlaps_hour(&self, day: NaiveDate, hour: u8) -> bool {
+        let start = day.and_hms(hour as u32, 0, 0);
+        self.overlaps(start, start + Duration::hours(1))
+    }
+
+    /// The line used to show this event in an hour box. The hour the event starts in shows its
+    /// times, later hours show that the event is still going.
+    pub fn hour_label(&self, day: NaiveDate, hour: u8) -> String {
+        let hour_start = day.and_hms(hour as u32, 0, 0);
+        if self.all_day {
+            self.title.clone()
+        } else if self.start >= hour_start {
+            format!("{} {}", self.time_range(), self.title)
+        } else {
+            format!("\u{2502} {}", self.title)
+        }
+    }
+
+    pub fn time_range(&self) -> String {
+        format!("{:02}:{:02}-{:02}:{:02}",
+                self.start.hour(), self.start.minute(),
+                self.end.hour(), self.end.minute())
+    }
+
+    /// Parses an event from the text written by `to_text`. `uid` is used if the text has no
+    /// `uid:` field.
+    pub fn parse(uid: &str, text: &str) -> Result<Event, String> {
+        let mut fields = Vec::new();
+        let mut lines = text.lines();
+        for line in &mut lines {
+            if line.trim().is_empty() {
+                break;
+            }
+            match line.find(':') {
+                Some(i) => fields.push((line[..i].trim(), line[i + 1..].trim())),
+                None => return Err(format!("expected `field: value`, got `{}`", line)),
+            }
+        }
+        let description = lines.collect::<Vec<_>>().join("\n");
+
+        let mut event = Event {
+            uid: String::from(uid),
+            title: String::new(),
+            start: NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0),
+            end: NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0),
+            all_day: false,
+            location: None,
+            description: match description.trim().is_empty() {
+                true => None,
+                false => Some(description),
+            },
+        };
+        let mut start = None;
+        let mut end = None;
+        let mut duration = None;
+        for (name, value) in fields {
+            match &name.to_lowercase()[..] {
+                "uid" => event.uid = String::from(value),
+                "title" => event.title = String::from(value),
+                "start" => start = Some(parse_datetime(value)?),
+                "end" => end = Some(parse_datetime(value)?),
+                "duration" => duration = Some(parse_duration(value)?),
+                "all-day" => event.all_day = parse_bool(value)?,
+                "location" if !value.is_empty() => event.location = Some(String::from(value)),
+                "location" => (),
+                _ => return Err(format!("unknown field `{}`", name)),
+            }
+        }
+
+        if event.title.is_empty() {
+            return Err(String::from("event has no title"));
+        }
+        event.start = match start {
+            Some(start) => start,
+            None => return Err(String::from("event has no start time")),
+        };
+        event.end = match (end, duration) {
+            (Some(end), _) => end,
+            (None, Some(duration)) => event.start.checked_add_signed(duration).ok_or_else(|| {
+                String::from("event ends too far in the future")
+            })?,
+            (None, None) if event.all_day => event.start + Duration::days(1),
+            (None, None) => event.start + Duration::hours(1),
+        };
+        if event.end < event.start {
+            return Err(String::from("event ends before it starts"));
+        }
+        Ok(event)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("uid: {}\n", self.uid));
+        text.push_str(&format!("title: {}\n", self.title));
+        text.push_str(&format!("start: {}\n", self.start.format(DATETIME_FORMAT)));
+        text.push_str(&format!("end: {}\n", self.end.format(DATETIME_FORMAT)));
+        if self.all_day {
+            text.push_str("all-day: yes\n");
+        }
+        text.push_str(&format!("location: {}\n", self.location.as_ref().map(|s| &s[..]).unwrap_or("")));
+        if let Some(ref description) = self.description {
+            text.push('\n');
+            text.push_str(description);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn parse_datetime(s: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).map_err(|e| {
+        format!("invalid time `{}` ({}), expected eg. `2017-05-31 09:30`", s, e)
+    })
+}
+
+fn parse_bool(s: &str) -> Result<bool, String> {
+    match &s.to_lowercase()[..] {
+        "yes" | "true" => Ok(true),
+        "no" | "false" | "" => Ok(false),
+        _ => Err(format!("expected `yes` or `no`, got `{}`", s)),
+    }
+}
+
+/// Parses durations such as `90m`, `2h` or `1h30m`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let too_long = || format!("duration `{}` is too long", s);
+    let mut minutes = 0i64;
+    let mut number = String::new();
+    for c in s.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' if !number.is_empty() => {
+                // Only digits were pushed, so the number can only fail to parse by being too big.
+                let n = number.parse::<i64>().map_err(|_| too_long())?;
+                let n = match c {
+                    'h' => n.checked_mul(60),
+                    _ => Some(n),
+                };
+                minutes = n.and_then(|n| minutes.checked_add(n)).ok_or_else(too_long)?;
+                number.clear();
+            },
+            _ => return Err(format!("invalid duration `{}`, expected eg. `1h30m`", s)),
+        }
+    }
+    if !number.is_empty() {
+        return Err(format!("invalid duration `{}`, expected eg. `1h30m`", s));
+    }
+    // `Duration` holds up to `i64::MAX` milliseconds and panics rather than overflowing.
+    minutes.checked_mul(60 * 1000).ok_or_else(too_long)?;
+    Ok(Duration::minutes(minutes))
+}
+
+/// A uid that's unique to this process and time, for new events and recurring entries.
+pub fn new_uid() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!("{}-{}-{}@callus",
+            Local::now().naive_local().format("%Y%m%dT%H%M%S"),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_with_duration(duration: &str) -> Result<Event, String> {
+        Event::parse("uid", &format!("title: Lunch\nstart: 2017-05-31 12:00\nduration: {}\n", duration))
+    }
+
+    #[test]
+    fn durations() {
+        let start = NaiveDate::from_ymd(2017, 5, 31).and_hms(12, 0, 0);
+        assert_eq!(parse_with_duration("90m").unwrap().end, start + Duration::minutes(90));
+        assert_eq!(parse_with_duration("1h 30m").unwrap().end, start + Duration::minutes(90));
+        assert!(parse_with_duration("1d").is_err());
+        assert!(parse_with_duration("10").is_err());
+    }
+
+    #[test]
+    fn durations_too_long() {
+        assert!(parse_with_duration("10000000000h").is_err());
+        assert!(parse_with_duration("99999999999999999999m").is_err());
+        assert!(parse_with_duration("153722867280912930m").is_err());
+        assert!(parse_with_duration("9223372036854775807m1m").is_err());
+        assert!(parse_with_duration("1000000000000m").is_err());
+    }
+}
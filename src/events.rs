@@ -0,0 +1,132 @@
+use chrono::offset::local::Local;
+use chrono::Timelike;
+
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
+
+use termion::event::{Event, Key, MouseEvent};
+use termion::input::TermRead;
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use ::storage::Change;
+
+/// Something the main loop has to respond to.
+pub enum Input {
+    Key(Key),
+    Mouse(MouseEvent),
+    /// The terminal was resized.
+    Resize,
+    /// A new minute started, so the current day or hour may have changed.
+    Tick,
+    /// Storage was changed from outside callus.
+    Changed(Change),
+    /// A reminder came due.
+    Reminder(String),
+    /// The terminal was closed.
+    End,
+}
+
+/// Merges keys, resizes, ticks and notifications from other threads into one stream of `Input`s
+/// for the main loop.
+pub struct Events {
+    inputs: Sender<Result<Input, io::Error>>,
+    rx: Receiver<Result<Input, io::Error>>,
+    carry_on: Sender<()>,
+    /// Whether the last input returned was a key or mouse event, which the reader is waiting to
+    /// hear has been dealt with.
+    key_pending: bool,
+}
+
+/// Lets other threads pass notifications to the main loop.
+#[derive(Clone)]
+pub struct Notifier(Sender<Result<Input, io::Error>>);
+
+impl Notifier {
+    /// Returns `false` once the main loop has stopped listening.
+    pub fn send(&self, input: Input) -> bool {
+        self.0.send(Ok(input)).is_ok()
+    }
+}
+
+impl Events {
+    pub fn new() -> Result<Events, io::Error> {
+        let (inputs, rx) = mpsc::channel();
+        let carry_on = read_keys(inputs.clone());
+        watch_resize(inputs.clone())?;
+        tick(inputs.clone());
+        Ok(Events {
+            inputs,
+            rx,
+            carry_on,
+            key_pending: false,
+        })
+    }
+
+    pub fn notifier(&self) -> Notifier {
+        Notifier(self.inputs.clone())
+    }
+
+    /// Waits for the next input. Keys and mouse events are only read once the previous one has
+    /// been dealt with, which is taken to be when this is called again.
+    pub fn next(&mut self) -> Result<Input, io::Error> {
+        if self.key_pending {
+            let _ = self.carry_on.send(());
+        }
+        // `self.inputs` keeps the channel open, so this can't fail.
+        let input = self.rx.recv().unwrap()?;
+        self.key_pending = matches!(input, Input::Key(..) | Input::Mouse(..));
+        Ok(input)
+    }
+}
+
+/// Reads keys and mouse events on another thread. After each one it waits to be told to carry on,
+/// so that it isn't reading the terminal at the same time as an editor started for that key.
+fn read_keys(inputs: Sender<Result<Input, io::Error>>) -> Sender<()> {
+    let (carry_on, carry_on_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for event in io::stdin().events() {
+            let input = match event {
+                Ok(Event::Key(key)) => Ok(Input::Key(key)),
+                Ok(Event::Mouse(mouse)) => Ok(Input::Mouse(mouse)),
+                Ok(Event::Unsupported(..)) => continue,
+                Err(e) => Err(e),
+            };
+            if inputs.send(input).is_err() || carry_on_rx.recv().is_err() {
+                return;
+            }
+        }
+        let _ = inputs.send(Ok(Input::End));
+    });
+    carry_on
+}
+
+fn watch_resize(inputs: Sender<Result<Input, io::Error>>) -> Result<(), io::Error> {
+    let mut signals = Signals::new([SIGWINCH])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if inputs.send(Ok(Input::Resize)).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Sends a tick at the start of every minute.
+fn tick(inputs: Sender<Result<Input, io::Error>>) {
+    thread::spawn(move || {
+        loop {
+            let now = Local::now();
+            // Leap seconds are counted as extra nanoseconds.
+            let nanos = now.nanosecond().min(999_999_999);
+            thread::sleep(Duration::new(59 - now.second().min(59) as u64, 1_000_000_000 - nanos));
+            if inputs.send(Ok(Input::Tick)).is_err() {
+                return;
+            }
+        }
+    });
+}
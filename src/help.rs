@@ -0,0 +1,86 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::{border, Widget, Block};
+use tui::style::{Modifier, Style};
+
+use termion::event::Key;
+
+use std::cell::Cell;
+use std::cmp::{min, max};
+
+use ::keys::Action;
+use ::theme::Theme;
+
+/// A popup listing the key bindings of the open view.
+pub struct HelpPane {
+    view: String,
+    bindings: Vec<(Action, Vec<String>)>,
+    scroll: usize,
+    /// The number of lines that fit, as of the last draw.
+    visible: Cell<usize>,
+}
+
+impl HelpPane {
+    pub fn new(view: String, bindings: Vec<(Action, Vec<String>)>) -> HelpPane {
+        HelpPane {
+            view,
+            bindings,
+            scroll: 0,
+            visible: Cell::new(1),
+        }
+    }
+
+    /// Scrolls the list. Returns whether the popup should be closed.
+    pub fn key(&mut self, key: Key) -> bool {
+        let last = self.bindings.len().saturating_sub(self.visible.get());
+        match key {
+            Key::Esc | Key::Char('q') | Key::Char('?') | Key::Char('\n') => return true,
+            Key::Up | Key::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.scroll = min(self.scroll + 1, last),
+            Key::Home | Key::Char('g') => self.scroll = 0,
+            Key::End | Key::Char('G') => self.scroll = last,
+            _ => (),
+        }
+        false
+    }
+
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer, theme: &Theme) {
+        let width = min(area.width, max(40, area.width * 3 / 5));
+        let height = min(area.height, self.bindings.len() as u16 + 2);
+        let rect = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        if rect.width < 3 || rect.height < 3 {
+            return;
+        }
+
+        for y in rect.top()..rect.bottom() {
+            for x in rect.left()..rect.right() {
+                buffer.get_mut(x, y).reset();
+            }
+        }
+        let title = format!("Keys in the {} view (Esc to close)", self.view);
+        let block = Block::default()
+                .title(&title)
+                .borders(border::ALL)
+                .title_style(theme.title.style());
+        block.draw(&rect, buffer);
+        let inner = block.inner(&rect);
+        self.visible.set(inner.height as usize);
+
+        let keys = self.bindings.iter().map(|(_, keys)| keys.join(", ")).collect::<Vec<_>>();
+        let keys_width = keys.iter().map(|keys| keys.chars().count()).max().unwrap_or(0);
+        let key_style = Style::default().modifier(Modifier::Bold);
+        for (i, &(action, _)) in self.bindings.iter().enumerate().skip(self.scroll).take(inner.height as usize) {
+            let y = inner.y + (i - self.scroll) as u16;
+            let keys = format!("{:width$}  ", keys[i], width = keys_width);
+            buffer.set_stringn(inner.x, y, &keys, inner.width as usize, &key_style);
+            let x = inner.x + min(inner.width, keys.chars().count() as u16);
+            let rest = (inner.right() - x) as usize;
+            buffer.set_stringn(x, y, action.description(), rest, &Style::default());
+        }
+    }
+}
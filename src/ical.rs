@@ -0,0 +1,874 @@
+use chrono::naive::date::NaiveDate;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::offset::local::Local;
+use chrono::{Datelike, Duration, TimeZone, Timelike};
+
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use ::event::Event;
+use ::database::Database;
+use ::error::Error;
+use ::recurrence::{nth_weekday_of_month, weekday_from_str, RRule, Recurring};
+use ::one_day;
+
+/// A `NAME;PARAM=VALUE:value` content line.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| &v[..])
+    }
+}
+
+/// A `BEGIN:NAME` ... `END:NAME` block, eg. a `VEVENT`.
+struct Component {
+    name: String,
+    properties: Vec<Property>,
+    components: Vec<Component>,
+}
+
+impl Component {
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// Every property called `name`, for those such as EXDATE that can be given more than once.
+    fn properties<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> + 'a {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.property(name).map(|p| &p.value[..])
+    }
+}
+
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Repeating events whose rule has parts recurring entries don't support.
+    pub skipped: usize,
+}
+
+/// The VEVENTs of an iCalendar file, converted to events and recurring entries.
+struct Imported {
+    events: Vec<Event>,
+    recurring: Vec<Recurring>,
+    skipped: usize,
+}
+
+/// Reads the VEVENTs from an iCalendar file into the database. Events are matched up by UID, and
+/// changed occurrences of repeating events by UID and RECURRENCE-ID, so importing the same file
+/// again only updates events that have changed. Repeating events become recurring entries.
+pub fn import(db: &Database, path: &Path) -> Result<ImportSummary, Error> {
+    let mut text = String::new();
+    let mut f = File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    f.read_to_string(&mut text)?;
+    let imported = parse_events(&text).map_err(|e| Error::Invalid(format!("{}: {}", path.display(), e)))?;
+
+    let mut existing = HashMap::new();
+    for event in db.all_events() {
+        existing.insert(event.uid.clone(), event);
+    }
+
+    let mut summary = ImportSummary {
+        added: 0,
+        updated: 0,
+        unchanged: 0,
+        skipped: imported.skipped,
+    };
+    for event in imported.events {
+        match existing.get(&event.uid) {
+            Some(old) if *old == event => {
+                summary.unchanged += 1;
+                continue;
+            },
+            Some(old) => {
+                db.delete_event(old)?;
+                summary.updated += 1;
+            },
+            None => summary.added += 1,
+        }
+        db.put_event(&event)?;
+        existing.insert(event.uid.clone(), event);
+    }
+
+    let existing = db.all_recurring();
+    for recurring in imported.recurring {
+        match existing.iter().find(|old| old.id == recurring.id) {
+            Some(old) if *old == recurring => {
+                summary.unchanged += 1;
+                continue;
+            },
+            Some(..) => summary.updated += 1,
+            None => summary.added += 1,
+        }
+        db.put_recurring(&recurring)?;
+    }
+    Ok(summary)
+}
+
+/// Writes the entries and events between `from` and `to` inclusive as an iCalendar file. Day
+/// notes become all-day events and hour entries become hour-long events. Recurring entries that
+/// occur in the range are written once, with their RRULE. Returns the number of
+/// VEVENTs written.
+pub fn export<W: Write>(db: &Database, from: NaiveDate, to: NaiveDate, out: &mut W) -> Result<usize, io::Error> {
+    let dtstamp = Local::now().naive_utc().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut count = 0;
+    let mut seen = HashSet::new();
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//callus//callus//EN"),
+    ];
+
+    for recurring in db.all_recurring() {
+        if !recurring_in_range(&recurring, from, to) {
+            continue;
+        }
+        let (start, end) = match recurring.hour {
+            Some(hour) => {
+                let start = recurring.start.and_hms(hour as u32, 0, 0);
+                (format!("DTSTART:{}", format_utc(start)),
+                 format!("DTEND:{}", format_utc(start + Duration::hours(1))))
+            },
+            None => (format!("DTSTART;VALUE=DATE:{}", recurring.start.format("%Y%m%d")),
+                     format!("DTEND;VALUE=DATE:{}", (recurring.start + one_day()).format("%Y%m%d"))),
+        };
+        let mut vevent = vevent_lines(&recurring.id, &dtstamp, &start, &end, &recurring.text);
+        let end_line = vevent.pop().unwrap();
+        vevent.push(format!("RRULE:{}", recurring.rule));
+        for exception in &recurring.exceptions {
+            vevent.push(match recurring.hour {
+                Some(hour) => format!("EXDATE:{}", format_utc(exception.and_hms(hour as u32, 0, 0))),
+                None => format!("EXDATE;VALUE=DATE:{}", exception.format("%Y%m%d")),
+            });
+        }
+        vevent.push(end_line);
+        lines.extend(vevent);
+        count += 1;
+    }
+
+    let mut day = from;
+    while day <= to {
+        let note = db.get_day_entry(day)?;
+        if !note.trim().is_empty() {
+            let uid = format!("{}-day@callus", day.format("%Y%m%d"));
+            let start = format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d"));
+            let end = format!("DTEND;VALUE=DATE:{}", (day + one_day()).format("%Y%m%d"));
+            lines.extend(vevent_lines(&uid, &dtstamp, &start, &end, &note));
+            count += 1;
+        }
+        for hour in 0..24 {
+            let entry = db.get_hour_entry(day, hour)?;
+            if entry.trim().is_empty() {
+                continue;
+            }
+            let start = day.and_hms(hour as u32, 0, 0);
+            let uid = format!("{}@callus", start.format("%Y%m%dT%H"));
+            let start_line = format!("DTSTART:{}", format_utc(start));
+            let end_line = format!("DTEND:{}", format_utc(start + Duration::hours(1)));
+            lines.extend(vevent_lines(&uid, &dtstamp, &start_line, &end_line, &entry));
+            count += 1;
+        }
+        for event in db.get_events(day) {
+            if event.start.date() < from || !seen.insert(event.uid.clone()) {
+                continue;
+            }
+            let (start, end) = match event.all_day {
+                true => (format!("DTSTART;VALUE=DATE:{}", event.start.format("%Y%m%d")),
+                         format!("DTEND;VALUE=DATE:{}", event.end.format("%Y%m%d"))),
+                false => (format!("DTSTART:{}", format_utc(event.start)),
+                          format!("DTEND:{}", format_utc(event.end))),
+            };
+            lines.push(String::from("BEGIN:VEVENT"));
+            lines.push(format!("UID:{}", event.uid));
+            lines.push(format!("DTSTAMP:{}", dtstamp));
+            lines.push(start);
+            lines.push(end);
+            lines.push(format!("SUMMARY:{}", escape(&event.title)));
+            if let Some(ref location) = event.location {
+                lines.push(format!("LOCATION:{}", escape(location)));
+            }
+            if let Some(ref description) = event.description {
+                lines.push(format!("DESCRIPTION:{}", escape(description)));
+            }
+            lines.push(String::from("END:VEVENT"));
+            count += 1;
+        }
+        day = day + one_day();
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    for line in lines {
+        out.write_all(fold(&line).as_bytes())?;
+    }
+    Ok(count)
+}
+
+fn recurring_in_range(recurring: &Recurring, from: NaiveDate, to: NaiveDate) -> bool {
+    let mut day = max(from, recurring.start);
+    while day <= to {
+        if recurring.occurs_on(day) {
+            return true;
+        }
+        day = day + one_day();
+    }
+    false
+}
+
+/// The lines of a VEVENT for a day or hour entry. The first line of the entry is its summary.
+fn vevent_lines(uid: &str, dtstamp: &str, start: &str, end: &str, text: &str) -> Vec<String> {
+    let summary = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    vec![
+        String::from("BEGIN:VEVENT"),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", dtstamp),
+        String::from(start),
+        String::from(end),
+        format!("SUMMARY:{}", escape(summary.trim())),
+        format!("DESCRIPTION:{}", escape(text.trim_end())),
+        String::from("END:VEVENT"),
+    ]
+}
+
+fn format_utc(local: NaiveDateTime) -> String {
+    let utc = match Local.from_local_datetime(&local).earliest() {
+        Some(datetime) => datetime.naive_utc(),
+        // The time was skipped by a daylight saving change, so it doesn't matter which side of
+        // the change it's treated as being on.
+        None => Local.from_utc_datetime(&local).naive_utc(),
+    };
+    utc.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            },
+            '\n' => out.push_str("\\n"),
+            '\r' => (),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Folds a content line so that no line is longer than 75 bytes, and adds the CRLF ending.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Parses the text of an iCalendar file and converts its VEVENTs to local time.
+fn parse_events(text: &str) -> Result<Imported, String> {
+    let components = parse(text)?;
+    let mut imported = Imported {
+        events: Vec::new(),
+        recurring: Vec::new(),
+        skipped: 0,
+    };
+    for calendar in components.iter().filter(|c| c.name == "VCALENDAR") {
+        let timezones = calendar.components.iter()
+            .filter(|c| c.name == "VTIMEZONE")
+            .filter_map(|c| c.value("TZID").map(|tzid| (String::from(tzid), c)))
+            .collect::<HashMap<_, _>>();
+        let mut repeating = Vec::new();
+        // The days of occurrences of repeating events that were changed or cancelled, by UID.
+        let mut overridden = HashMap::new();
+        for vevent in calendar.components.iter().filter(|c| c.name == "VEVENT") {
+            let cancelled = vevent.value("STATUS") == Some("CANCELLED");
+            match vevent.property("RECURRENCE-ID") {
+                // A changed occurrence is skipped by the recurring entry and, unless it was
+                // cancelled, kept as an event of its own.
+                Some(recurrence_id) => {
+                    let day = parse_time(recurrence_id, &timezones)?.0.date();
+                    let uid = vevent.value("UID").unwrap_or("");
+                    overridden.entry(uid).or_insert_with(Vec::new).push(day);
+                    if !cancelled {
+                        let mut event = vevent_to_event(vevent, &timezones)?;
+                        event.uid = format!("{}-{}", event.uid, recurrence_id.value);
+                        imported.events.push(event);
+                    }
+                },
+                None if cancelled => (),
+                None if vevent.property("RRULE").is_some() => repeating.push(vevent),
+                None => imported.events.push(vevent_to_event(vevent, &timezones)?),
+            }
+        }
+        for vevent in repeating {
+            let overridden = overridden.get(vevent.value("UID").unwrap_or("")).map(|days| &days[..]).unwrap_or(&[]);
+            match vevent_to_recurring(vevent, &timezones, overridden)? {
+                Some(recurring) => imported.recurring.push(recurring),
+                None => imported.skipped += 1,
+            }
+        }
+    }
+    Ok(imported)
+}
+
+fn vevent_to_event(vevent: &Component, timezones: &HashMap<String, &Component>) -> Result<Event, String> {
+    let dtstart = match vevent.property("DTSTART") {
+        Some(dtstart) => dtstart,
+        None => return Err(String::from("VEVENT has no DTSTART")),
+    };
+    let (start, all_day) = parse_time(dtstart, timezones)?;
+    let end = match (vevent.property("DTEND"), vevent.value("DURATION")) {
+        (Some(dtend), _) => parse_time(dtend, timezones)?.0,
+        (None, Some(duration)) => start.checked_add_signed(parse_duration(duration)?).ok_or_else(|| {
+            format!("DURATION `{}` ends too far from DTSTART `{}`", duration, dtstart.value)
+        })?,
+        (None, None) if all_day => start.checked_add_signed(Duration::days(1)).ok_or_else(|| {
+            format!("DTSTART `{}` is too late", dtstart.value)
+        })?,
+        (None, None) => start,
+    };
+    if end < start {
+        return Err(format!("VEVENT `{}` ends before it starts",
+                           vevent.value("SUMMARY").or(vevent.value("UID")).unwrap_or(&dtstart.value)));
+    }
+
+    // Events are stored to the minute.
+    let start = start.with_second(0).unwrap_or(start);
+    let end = end.with_second(0).unwrap_or(end);
+
+    // Events are stored as `field: value` lines, so the title and location have to fit on one
+    // line and the description can't end with blank lines, or the event wouldn't read back the
+    // same.
+    let title = vevent.value("SUMMARY")
+        .map(|s| one_line(&unescape(s)))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| String::from("(no title)"));
+    let uid = match vevent.value("UID") {
+        Some(uid) => String::from(uid),
+        None => format!("{:016x}@callus", fnv1a(&format!("{}{}", dtstart.value, title))),
+    };
+    Ok(Event {
+        uid,
+        title,
+        start,
+        end,
+        all_day,
+        location: vevent.value("LOCATION").map(|s| one_line(&unescape(s))).filter(|s| !s.is_empty()),
+        description: vevent.value("DESCRIPTION")
+            .map(|s| String::from(unescape(s).trim_end()))
+            .filter(|s| !s.trim().is_empty()),
+    })
+}
+
+/// Converts a VEVENT with an RRULE to a recurring entry at the hour it starts, with its times in
+/// the text if it doesn't fill the hour. Occurrences in EXDATEs or in `overridden` are skipped.
+/// Returns `None` if the rule can't be represented.
+fn vevent_to_recurring(vevent: &Component, timezones: &HashMap<String, &Component>, overridden: &[NaiveDate])
+    -> Result<Option<Recurring>, String>
+{
+    let rule = match vevent.value("RRULE").map(RRule::parse) {
+        Some(Ok(rule)) => rule,
+        _ => return Ok(None),
+    };
+    let event = vevent_to_event(vevent, timezones)?;
+
+    let mut exceptions = overridden.to_vec();
+    for exdate in vevent.properties("EXDATE") {
+        for value in exdate.value.split(',') {
+            let property = Property {
+                name: exdate.name.clone(),
+                params: exdate.params.clone(),
+                value: String::from(value),
+            };
+            exceptions.push(parse_time(&property, timezones)?.0.date());
+        }
+    }
+    exceptions.sort();
+    exceptions.dedup();
+
+    let mut text = match event.all_day || (event.start.minute() == 0 && event.end == event.start + Duration::hours(1)) {
+        true => event.title.clone(),
+        false => format!("{} {}", event.time_range(), event.title),
+    };
+    if let Some(ref location) = event.location {
+        text.push_str(&format!(" @ {}", location));
+    }
+    if let Some(ref description) = event.description {
+        text.push('\n');
+        text.push_str(description.trim_end());
+    }
+
+    Ok(Some(Recurring {
+        id: event.uid,
+        rule,
+        start: event.start.date(),
+        hour: match event.all_day {
+            true => None,
+            false => Some(event.start.hour() as u8),
+        },
+        exceptions,
+        text,
+    }))
+}
+
+fn parse(text: &str) -> Result<Vec<Component>, String> {
+    // Long lines are folded by starting the following line with a space or tab.
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(String::from(line));
+        }
+    }
+
+    let mut stack = vec![Component {
+        name: String::new(),
+        properties: Vec::new(),
+        components: Vec::new(),
+    }];
+    for line in lines {
+        let property = parse_property(&line)?;
+        match &property.name[..] {
+            "BEGIN" => stack.push(Component {
+                name: property.value.to_uppercase(),
+                properties: Vec::new(),
+                components: Vec::new(),
+            }),
+            "END" => {
+                if stack.len() < 2 || stack[stack.len() - 1].name != property.value.to_uppercase() {
+                    return Err(format!("unexpected END:{}", property.value));
+                }
+                let component = stack.pop().unwrap();
+                stack.last_mut().unwrap().components.push(component);
+            },
+            _ => stack.last_mut().unwrap().properties.push(property),
+        }
+    }
+    if stack.len() != 1 {
+        return Err(format!("missing END:{}", stack[stack.len() - 1].name));
+    }
+    Ok(stack.pop().unwrap().components)
+}
+
+fn parse_property(line: &str) -> Result<Property, String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quoted = false;
+    let mut value = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => parts.push(::std::mem::take(&mut part)),
+            ':' if !quoted => {
+                parts.push(::std::mem::take(&mut part));
+                value = Some(&line[i + 1..]);
+                break;
+            },
+            c => part.push(c),
+        }
+    }
+    let value = match value {
+        Some(value) => value,
+        None => return Err(format!("invalid line `{}`", line)),
+    };
+
+    let mut parts = parts.into_iter();
+    let name = parts.next().unwrap_or_default().to_uppercase();
+    let params = parts.map(|param| match param.find('=') {
+        Some(i) => (param[..i].to_uppercase(), String::from(&param[i + 1..])),
+        None => (param.to_uppercase(), String::new()),
+    }).collect();
+    Ok(Property {
+        name,
+        params,
+        value: String::from(value),
+    })
+}
+
+/// `s` with its lines joined by spaces and the whitespace around them removed.
+fn one_line(s: &str) -> String {
+    s.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => (),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Converts a DTSTART/DTEND property to local time. The returned flag is set for dates without
+/// a time, which mark all-day events.
+fn parse_time(property: &Property, timezones: &HashMap<String, &Component>) -> Result<(NaiveDateTime, bool), String> {
+    let value = &property.value[..];
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|e| {
+            format!("invalid date `{}`: {}", value, e)
+        })?;
+        return Ok((date.and_hms(0, 0, 0), true));
+    }
+
+    let utc = value.ends_with('Z');
+    let time = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").map_err(|e| {
+        format!("invalid date-time `{}`: {}", value, e)
+    })?;
+    let utc_time = match (utc, property.param("TZID")) {
+        (true, _) => time,
+        (false, Some(tzid)) => match timezones.get(tzid.trim_matches('"')) {
+            Some(vtimezone) => time - Duration::seconds(utc_offset(vtimezone, time) as i64),
+            None if is_utc_alias(tzid) => time,
+            // Without a VTIMEZONE to say otherwise, treat the time as local time.
+            None => return Ok((time, false)),
+        },
+        // A "floating" time, which is the same wall-clock time in every timezone.
+        (false, None) => return Ok((time, false)),
+    };
+    Ok((Local.from_utc_datetime(&utc_time).naive_local(), false))
+}
+
+fn is_utc_alias(tzid: &str) -> bool {
+    matches!(tzid.trim_matches('"'), "UTC" | "GMT" | "Etc/UTC" | "Etc/GMT" | "Z")
+}
+
+/// Works out the offset from UTC, in seconds, of a local time in a VTIMEZONE. Each STANDARD and
+/// DAYLIGHT observance starts at its DTSTART and, if it has a yearly RRULE, again every year. The
+/// observance that started most recently is the one in effect.
+fn utc_offset(vtimezone: &Component, time: NaiveDateTime) -> i32 {
+    let mut current: Option<(NaiveDateTime, i32)> = None;
+    let mut earliest: Option<(NaiveDateTime, i32)> = None;
+    for observance in &vtimezone.components {
+        let offset_to = observance.value("TZOFFSETTO").and_then(parse_offset);
+        let offset_from = observance.value("TZOFFSETFROM").and_then(parse_offset);
+        let dtstart = observance.value("DTSTART").and_then(|s| {
+            NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").ok()
+        });
+        let (offset_to, dtstart) = match (offset_to, dtstart) {
+            (Some(offset_to), Some(dtstart)) => (offset_to, dtstart),
+            _ => continue,
+        };
+        if earliest.is_none_or(|(start, _)| dtstart < start) {
+            earliest = Some((dtstart, offset_from.unwrap_or(offset_to)));
+        }
+
+        let mut onsets = vec![dtstart];
+        if let Some(rrule) = observance.value("RRULE") {
+            for year in [time.year() - 1, time.year()].iter() {
+                if let Some(onset) = yearly_onset(rrule, *year, dtstart) {
+                    onsets.push(onset);
+                }
+            }
+        }
+        for onset in onsets {
+            if onset >= dtstart && onset <= time && current.is_none_or(|(start, _)| onset > start) {
+                current = Some((onset, offset_to));
+            }
+        }
+    }
+    match (current, earliest) {
+        (Some((_, offset)), _) => offset,
+        (None, Some((_, offset))) => offset,
+        (None, None) => 0,
+    }
+}
+
+/// Finds when a yearly `FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU` style rule fires in `year`.
+fn yearly_onset(rrule: &str, year: i32, dtstart: NaiveDateTime) -> Option<NaiveDateTime> {
+    let mut month = dtstart.month();
+    let mut byday = None;
+    for part in rrule.split(';') {
+        let mut kv = part.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("FREQ"), Some(freq)) if freq != "YEARLY" => return None,
+            (Some("BYMONTH"), Some(m)) => month = m.parse().ok()?,
+            (Some("BYDAY"), Some(d)) => byday = Some(d),
+            _ => (),
+        }
+    }
+
+    let date = match byday {
+        Some(byday) => {
+            let split = byday.len().checked_sub(2)?;
+            let weekday = weekday_from_str(byday.get(split..)?)?;
+            let n = match byday.get(..split)? {
+                "" => 1,
+                n => n.trim_start_matches('+').parse::<i32>().ok()?,
+            };
+            nth_weekday_of_month(year, month, weekday, n)?
+        },
+        None => NaiveDate::from_ymd_opt(year, month, dtstart.day())?,
+    };
+    Some(date.and_hms(dtstart.hour(), dtstart.minute(), dtstart.second()))
+}
+
+/// Parses a UTC offset such as `+0100` or `-053000` into seconds.
+fn parse_offset(s: &str) -> Option<i32> {
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = &s[1..];
+    if digits.len() < 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours = digits[0..2].parse::<i32>().ok()?;
+    let minutes = digits[2..4].parse::<i32>().ok()?;
+    let seconds = digits.get(4..6).and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Parses an RFC 5545 duration such as `PT1H30M` or `P1D`. Durations too long for a `Duration`
+/// are errors.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{}`", s);
+    let too_long = || format!("duration `{}` is too long", s);
+    let (negative, rest) = match s.chars().next() {
+        Some('-') => (true, &s[1..]),
+        Some('+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    if !rest.starts_with('P') {
+        return Err(invalid());
+    }
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    for c in rest[1..].chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => (),
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n = number.parse::<i64>().map_err(|_| match number.is_empty() {
+                    true => invalid(),
+                    false => too_long(),
+                })?;
+                let unit = match c {
+                    'W' => 7 * 24 * 3600,
+                    'D' => 24 * 3600,
+                    'H' => 3600,
+                    'M' => 60,
+                    _ => 1,
+                };
+                seconds = n.checked_mul(unit).and_then(|n| seconds.checked_add(n)).ok_or_else(too_long)?;
+                number.clear();
+            },
+            _ => return Err(invalid()),
+        }
+    }
+    // `Duration` holds up to `i64::MAX` milliseconds and panics rather than overflowing.
+    seconds.checked_mul(1000).ok_or_else(too_long)?;
+    Ok(Duration::seconds(if negative { -seconds } else { seconds }))
+}
+
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(utc: NaiveDateTime) -> NaiveDateTime {
+        Local.from_utc_datetime(&utc).naive_local()
+    }
+
+    fn calendar(body: &str) -> String {
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n", body)
+    }
+
+    #[test]
+    fn unfolds_lines() {
+        let text = "BEGIN:VEVENT\r\nSUMMARY:A very\r\n  long title\r\nDESCRIPTION:one\\n\r\n\ttwo\r\nEND:VEVENT\r\n";
+        let components = parse(text).unwrap();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].value("SUMMARY"), Some("A very long title"));
+        assert_eq!(components[0].value("DESCRIPTION").map(unescape), Some(String::from("one\ntwo")));
+    }
+
+    #[test]
+    fn folding_round_trips() {
+        let line = format!("DESCRIPTION:{}", "é".repeat(100));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        let components = parse(&format!("BEGIN:VEVENT\r\n{}END:VEVENT\r\n", folded)).unwrap();
+        assert_eq!(components[0].value("DESCRIPTION"), Some(&line["DESCRIPTION:".len()..]));
+    }
+
+    #[test]
+    fn times() {
+        let imported = parse_events(&calendar("\
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+END:STANDARD\r
+BEGIN:DAYLIGHT\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+END:DAYLIGHT\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:summer\r
+DTSTART;TZID=Europe/Berlin:20170531T150000\r
+DTEND;TZID=Europe/Berlin:20170531T160000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:winter\r
+DTSTART;TZID=\"Europe/Berlin\":20171231T150000\r
+DURATION:PT30M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:utc\r
+DTSTART:20170531T150000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:floating\r
+DTSTART:20170531T150000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:all-day\r
+DTSTART;VALUE=DATE:20170531\r
+END:VEVENT\r
+")).unwrap();
+        let events = imported.events.iter().map(|e| (&e.uid[..], e)).collect::<HashMap<_, _>>();
+        let at = |y, m, d, h, min| NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0);
+
+        assert_eq!(events["summer"].start, local(at(2017, 5, 31, 13, 0)));
+        assert_eq!(events["summer"].end, local(at(2017, 5, 31, 14, 0)));
+        assert_eq!(events["winter"].start, local(at(2017, 12, 31, 14, 0)));
+        assert_eq!(events["winter"].end, local(at(2017, 12, 31, 14, 30)));
+        assert_eq!(events["utc"].start, local(at(2017, 5, 31, 15, 0)));
+        assert_eq!(events["floating"].start, at(2017, 5, 31, 15, 0));
+        assert!(events["all-day"].all_day);
+        assert_eq!(events["all-day"].start, at(2017, 5, 31, 0, 0));
+        assert_eq!(events["all-day"].end, at(2017, 6, 1, 0, 0));
+    }
+
+    #[test]
+    fn repeating_events() {
+        let imported = parse_events(&calendar("\
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART:20170529T093000\r
+DTEND:20170529T094500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE\r
+EXDATE:20170605T093000,20170607T093000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID:20170612T093000\r
+SUMMARY:Standup (moved)\r
+DTSTART:20170612T110000\r
+DTEND:20170612T111500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:odd\r
+DTSTART:20170529T093000\r
+RRULE:FREQ=WEEKLY;BYSETPOS=1\r
+END:VEVENT\r
+")).unwrap();
+        assert_eq!(imported.skipped, 1);
+        assert_eq!(imported.recurring.len(), 1);
+        let recurring = &imported.recurring[0];
+        assert_eq!(recurring.id, "standup");
+        assert_eq!(recurring.hour, Some(9));
+        assert_eq!(recurring.text, "09:30-09:45 Standup");
+        assert_eq!(recurring.exceptions, vec![NaiveDate::from_ymd(2017, 6, 5), NaiveDate::from_ymd(2017, 6, 7),
+                                              NaiveDate::from_ymd(2017, 6, 12)]);
+        assert!(recurring.occurs_on(NaiveDate::from_ymd(2017, 5, 31)));
+        assert!(!recurring.occurs_on(NaiveDate::from_ymd(2017, 6, 5)));
+
+        assert_eq!(imported.events.len(), 1);
+        assert_eq!(imported.events[0].uid, "standup-20170612T093000");
+        assert_eq!(imported.events[0].title, "Standup (moved)");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("PT1H30M"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W2DT3H"), Ok(Duration::hours(9 * 24 + 3)));
+        assert_eq!(parse_duration("-PT15M"), Ok(Duration::minutes(-15)));
+        assert!(parse_duration("PT").is_ok());
+        assert!(parse_duration("1H").is_err());
+        assert!(parse_duration("PTH").is_err());
+        assert!(parse_duration("P99999999999W").is_err());
+        assert!(parse_duration("P999999999999999999999D").is_err());
+        assert!(parse_duration("PT9223372036854776S").is_err());
+        assert!(parse_duration("PT9223372036854775807S1S").is_err());
+    }
+
+    #[test]
+    fn bad_durations_fail_the_import() {
+        let event = |end: &str| calendar(&format!("BEGIN:VEVENT\r\nUID:a\r\nDTSTART:20170531T150000\r\n{}\r\nEND:VEVENT\r\n", end));
+        assert!(parse_events(&event("DURATION:PT2H")).is_ok());
+        assert!(parse_events(&event("DURATION:P99999999999W")).is_err());
+        assert!(parse_events(&event("DURATION:P9999999999D")).is_err());
+        assert!(parse_events(&event("DURATION:-PT1H")).is_err());
+        assert!(parse_events(&event("DTEND:20170531T140000")).is_err());
+    }
+
+    #[test]
+    fn events_read_back_as_imported() {
+        let imported = parse_events(&calendar("\
+BEGIN:VEVENT\r
+UID:a\r
+SUMMARY: Two\\nlines \r
+LOCATION:Room 1\\n\\nFirst floor\r
+DESCRIPTION:\\nSome\\n\\ndescription\\n\\n\r
+DTSTART:20170531T150000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:b\r
+SUMMARY:\r
+DTSTART:20170531T150000\r
+END:VEVENT\r
+")).unwrap();
+        let a = &imported.events[0];
+        assert_eq!(a.title, "Two lines");
+        assert_eq!(a.location, Some(String::from("Room 1 First floor")));
+        assert_eq!(a.description, Some(String::from("\nSome\n\ndescription")));
+        assert_eq!(imported.events[1].title, "(no title)");
+        for event in &imported.events {
+            assert_eq!(Event::parse(&event.uid, &event.to_text()).as_ref(), Ok(event));
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use termion::event::Key;
+
+use std::collections::HashMap;
+
+use ::config::KeysConfig;
+use ::error::Error;
+
+/// Something a key sequence can be bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Edit the selected entry, inline or in the editor depending on the `inline_editor` setting.
+    Edit,
+    EditInline,
+    EditExternal,
+    NewEvent,
+    EditEvent,
+    NewRecurring,
+    EditRecurring,
+    SkipOccurrence,
+    Import,
+    Search,
+    Command,
+    NextView,
+    PrevView,
+    Today,
+    Goto,
+    Add,
+    NextMonth,
+    PrevMonth,
+    NextYear,
+    PrevYear,
+    Help,
+    Quit,
+    /// Does nothing. Used to unbind a default binding.
+    None,
+}
+
+/// Every action in the order the help lists them, with its name in the config file and a
+/// description.
+const ACTIONS: [(Action, &str, &str); 27] = [
+    (Action::Left, "left", "move left"),
+    (Action::Right, "right", "move right"),
+    (Action::Up, "up", "move up"),
+    (Action::Down, "down", "move down"),
+    (Action::NextView, "next_view", "zoom in to the next view"),
+    (Action::PrevView, "prev_view", "zoom out to the previous view"),
+    (Action::Today, "today", "go to today"),
+    (Action::Goto, "goto", "go to a date"),
+    (Action::NextMonth, "next_month", "forward a month"),
+    (Action::PrevMonth, "prev_month", "back a month"),
+    (Action::NextYear, "next_year", "forward a year"),
+    (Action::PrevYear, "prev_year", "back a year"),
+    (Action::Add, "add", "add an entry, eg. `lunch tomorrow 1pm`"),
+    (Action::Edit, "edit", "edit the selected entry"),
+    (Action::EditInline, "edit_inline", "edit the selected entry in a popup"),
+    (Action::EditExternal, "edit_external", "edit the selected entry in the editor"),
+    (Action::NewEvent, "new_event", "add an event"),
+    (Action::EditEvent, "edit_event", "edit an event"),
+    (Action::NewRecurring, "new_recurring", "add a recurring entry"),
+    (Action::EditRecurring, "edit_recurring", "edit a recurring entry"),
+    (Action::SkipOccurrence, "skip_occurrence", "skip recurring entries on this day"),
+    (Action::Import, "import", "import an iCalendar file"),
+    (Action::Search, "search", "search entries"),
+    (Action::Command, "command", "open the command line"),
+    (Action::Help, "help", "show these bindings"),
+    (Action::Quit, "quit", "quit"),
+    (Action::None, "none", "nothing"),
+];
+
+const DEFAULT_BINDINGS: [(&str, &str); 30] = [
+    ("h", "left"),
+    ("<Left>", "left"),
+    ("l", "right"),
+    ("<Right>", "right"),
+    ("k", "up"),
+    ("<Up>", "up"),
+    ("j", "down"),
+    ("<Down>", "down"),
+    (">", "next_view"),
+    ("<", "prev_view"),
+    ("t", "today"),
+    ("g", "goto"),
+    ("<PageDown>", "next_month"),
+    ("<PageUp>", "prev_month"),
+    ("]", "next_year"),
+    ("[", "prev_year"),
+    ("a", "add"),
+    ("<Enter>", "edit"),
+    ("i", "edit_inline"),
+    ("e", "edit_external"),
+    ("n", "new_event"),
+    ("o", "edit_event"),
+    ("r", "new_recurring"),
+    ("R", "edit_recurring"),
+    ("x", "skip_occurrence"),
+    ("I", "import"),
+    ("/", "search"),
+    (":", "command"),
+    ("?", "help"),
+    ("q", "quit"),
+];
+
+const VIEWS: [&str; 4] = ["year", "month", "week", "day"];
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        ACTIONS.iter().find(|&&(action, _, _)| action == self).unwrap().2
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|&&(_, n, _)| n == name).map(|&(action, _, _)| action)
+    }
+}
+
+/// What a sequence of keys is bound to.
+pub enum Lookup {
+    Action(Action),
+    /// The keys are the start of a longer binding, so wait for more.
+    Pending,
+    Unbound,
+}
+
+/// The key bindings: the defaults, then the `[keys]` section of the config file, then the
+/// bindings for the view that's open.
+pub struct Keymap {
+    global: HashMap<Vec<Key>, Action>,
+    views: HashMap<&'static str, HashMap<Vec<Key>, Action>>,
+}
+
+impl Keymap {
+    pub fn new(config: &KeysConfig) -> Result<Keymap, Error> {
+        let mut global = HashMap::new();
+        bind(&mut global, DEFAULT_BINDINGS.iter().cloned())?;
+        bind(&mut global, config.global.iter().map(|(keys, action)| (&keys[..], &action[..])))?;
+
+        let mut views = HashMap::new();
+        for &view in VIEWS.iter() {
+            let bindings = match view {
+                "year" => &config.year,
+                "month" => &config.month,
+                "week" => &config.week,
+                _ => &config.day,
+            };
+            let mut map = HashMap::new();
+            bind(&mut map, bindings.iter().map(|(keys, action)| (&keys[..], &action[..])))?;
+            views.insert(view, map);
+        }
+        Ok(Keymap {
+            global,
+            views,
+        })
+    }
+
+    /// The action `keys` are bound to in `view`, which is named as in the config file.
+    pub fn action(&self, view: &str, keys: &[Key]) -> Option<Action> {
+        self.views.get(view)
+            .and_then(|map| map.get(keys))
+            .or_else(|| self.global.get(keys))
+            .cloned()
+            .filter(|&action| action != Action::None)
+    }
+
+    pub fn lookup(&self, view: &str, keys: &[Key]) -> Lookup {
+        let view_map = self.views.get(view);
+        let longer = view_map.into_iter().flat_map(|map| map.keys())
+            .chain(self.global.keys())
+            .any(|bound| bound.len() > keys.len() && bound.starts_with(keys) && self.action(view, bound).is_some());
+        match (longer, self.action(view, keys)) {
+            (true, _) => Lookup::Pending,
+            (false, Some(action)) => Lookup::Action(action),
+            (false, None) => Lookup::Unbound,
+        }
+    }
+
+    /// Every action bound in `view` with the keys bound to it, in the order they're listed in the
+    /// help.
+    pub fn bindings(&self, view: &str) -> Vec<(Action, Vec<String>)> {
+        let mut bound = self.global.keys()
+            .chain(self.views.get(view).into_iter().flat_map(|map| map.keys()))
+            .filter_map(|keys| self.action(view, keys).map(|action| (action, keys_name(keys))))
+            .collect::<Vec<_>>();
+        // Plain keys before named ones.
+        bound.sort_by(|(_, a), (_, b)| (a.len(), a).cmp(&(b.len(), b)));
+        bound.dedup();
+
+        let mut bindings = Vec::new();
+        for &(action, _, _) in ACTIONS.iter() {
+            let keys = bound.iter()
+                .filter(|&&(a, _)| a == action)
+                .map(|(_, keys)| keys.clone())
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                bindings.push((action, keys));
+            }
+        }
+        bindings
+    }
+
+    /// The first key sequence bound to `action` in `view`, if any.
+    pub fn first_key(&self, view: &str, action: Action) -> Option<String> {
+        self.bindings(view).into_iter().find(|&(a, _)| a == action).map(|(_, keys)| keys[0].clone())
+    }
+}
+
+fn bind<'a, I>(map: &mut HashMap<Vec<Key>, Action>, bindings: I) -> Result<(), Error>
+    where I: Iterator<Item = (&'a str, &'a str)>
+{
+    for (keys, name) in bindings {
+        let parsed = parse_keys(keys)
+            .ok_or_else(|| Error::Config(format!("invalid key sequence `{}`", keys)))?;
+        let action = Action::from_name(name)
+            .ok_or_else(|| Error::Config(format!("unknown action `{}` for `{}`", name, keys)))?;
+        map.insert(parsed, action);
+    }
+    Ok(())
+}
+
+/// Parses a key sequence written like `gg`, `<C-d>` or `<Space>t`. Each character is a key, apart
+/// from special keys which are named in angle brackets. A `<` that doesn't start a name is just a
+/// `<`, and `<lt>` can be used where it would.
+pub fn parse_keys(s: &str) -> Option<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if let Some(key) = parse_special(&rest[1..end]) {
+                    keys.push(key);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        keys.push(Key::Char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    match keys.is_empty() {
+        true => None,
+        false => Some(keys),
+    }
+}
+
+fn parse_special(name: &str) -> Option<Key> {
+    let lower = name.to_lowercase();
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if lower.starts_with("c-") {
+        return single(&name[2..]).map(|c| Key::Ctrl(c.to_ascii_lowercase()));
+    }
+    if lower.starts_with("a-") || lower.starts_with("m-") {
+        return single(&name[2..]).map(Key::Alt);
+    }
+    if let Some(Ok(n)) = lower.strip_prefix('f').map(str::parse) {
+        return Some(Key::F(n));
+    }
+    Some(match &lower[..] {
+        "enter" | "cr" | "return" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "space" => Key::Char(' '),
+        "lt" => Key::Char('<'),
+        "esc" => Key::Esc,
+        "bs" | "backspace" => Key::Backspace,
+        "del" | "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => return None,
+    })
+}
+
+/// How a key sequence is written in the config file and the help.
+pub fn keys_name(keys: &[Key]) -> String {
+    keys.iter().map(|&key| key_name(key)).collect()
+}
+
+/// How a key is written in the config file and the help.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => String::from("<Enter>"),
+        Key::Char('\t') => String::from("<Tab>"),
+        Key::Char(' ') => String::from("<Space>"),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("<C-{}>", c),
+        Key::Alt(c) => format!("<A-{}>", c),
+        Key::F(n) => format!("<F{}>", n),
+        Key::Esc => String::from("<Esc>"),
+        Key::Backspace => String::from("<BS>"),
+        Key::Delete => String::from("<Del>"),
+        Key::Insert => String::from("<Insert>"),
+        Key::Left => String::from("<Left>"),
+        Key::Right => String::from("<Right>"),
+        Key::Up => String::from("<Up>"),
+        Key::Down => String::from("<Down>"),
+        Key::Home => String::from("<Home>"),
+        Key::End => String::from("<End>"),
+        Key::PageUp => String::from("<PageUp>"),
+        Key::PageDown => String::from("<PageDown>"),
+        _ => String::from("<?>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::event::Key;
+    use toml;
+
+    use ::config::{Config, KeysConfig};
+    use super::{keys_name, parse_keys, Action, Keymap, Lookup};
+
+    fn keymap(config: &str) -> Keymap {
+        let config: Config = toml::from_str(config).unwrap();
+        Keymap::new(&config.keys).unwrap()
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(parse_keys("gg"), Some(vec![Key::Char('g'), Key::Char('g')]));
+        assert_eq!(parse_keys("<C-D>"), Some(vec![Key::Ctrl('d')]));
+        assert_eq!(parse_keys("<Space>t"), Some(vec![Key::Char(' '), Key::Char('t')]));
+        assert_eq!(parse_keys("<F5><esc>"), Some(vec![Key::F(5), Key::Esc]));
+        assert_eq!(parse_keys("<lt>"), Some(vec![Key::Char('<')]));
+        assert_eq!(parse_keys("<"), Some(vec![Key::Char('<')]));
+        assert_eq!(parse_keys("<nope>"), Some("<nope>".chars().map(Key::Char).collect()));
+        assert_eq!(parse_keys(""), None);
+        for keys in &["gg", "<C-d>", "<Space>t", "<Enter>", "<PageDown>", "<A-x>"] {
+            assert_eq!(keys_name(&parse_keys(keys).unwrap()), *keys);
+        }
+    }
+
+    #[test]
+    fn defaults_and_config() {
+        let keymap = keymap(r#"
+            [keys]
+            "gg" = "today"
+            "q" = "none"
+            "<C-q>" = "quit"
+
+            [keys.month]
+            "J" = "next_month"
+            "h" = "none"
+        "#);
+        let action = |view, keys| keymap.action(view, &parse_keys(keys).unwrap());
+        assert_eq!(action("week", "j"), Some(Action::Down));
+        assert_eq!(action("week", "gg"), Some(Action::Today));
+        assert_eq!(action("week", "q"), None);
+        assert_eq!(action("week", "<C-q>"), Some(Action::Quit));
+        assert_eq!(action("month", "J"), Some(Action::NextMonth));
+        assert_eq!(action("week", "J"), None);
+        assert_eq!(action("month", "h"), None);
+        assert_eq!(action("day", "h"), Some(Action::Left));
+        assert_eq!(keymap.first_key("week", Action::Quit), Some(String::from("<C-q>")));
+        assert_eq!(keymap.first_key("week", Action::Left), Some(String::from("h")));
+        assert_eq!(keymap.first_key("month", Action::Left), Some(String::from("<Left>")));
+    }
+
+    #[test]
+    fn sequences() {
+        let keymap = keymap("[keys]\n\"gg\" = \"today\"\n");
+        // `g` on its own is still bound, but waits to see whether `gg` follows.
+        assert!(matches!(keymap.lookup("day", &[Key::Char('g')]), Lookup::Pending));
+        assert!(matches!(keymap.lookup("day", &[Key::Char('g'), Key::Char('g')]), Lookup::Action(Action::Today)));
+        assert!(matches!(keymap.lookup("day", &[Key::Char('z')]), Lookup::Unbound));
+        assert!(matches!(keymap.lookup("day", &[Key::Char('j')]), Lookup::Action(Action::Down)));
+    }
+
+    #[test]
+    fn bad_bindings() {
+        let mut config = KeysConfig::default();
+        config.global.insert(String::from("x"), String::from("fly"));
+        assert!(Keymap::new(&config).is_err());
+        let mut config = KeysConfig::default();
+        config.week.insert(String::from(""), String::from("quit"));
+        assert!(Keymap::new(&config).is_err());
+    }
+}
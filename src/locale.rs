@@ -0,0 +1,243 @@
+use chrono::naive::date::NaiveDate;
+use chrono::{Datelike, Weekday};
+
+use std::env;
+use std::sync::OnceLock;
+
+use ::one_day;
+use ::config::LocaleConfig;
+use ::dates;
+use ::error::Error;
+
+/// Day names starting with Monday, month names, and how the day of the month is written, for each
+/// built-in language.
+const LANGUAGES: [(&str, [&str; 7], [&str; 12], Ordinal); 8] = [
+    ("en", ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+     ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+     Ordinal::English),
+    ("de", ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+     ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"],
+     Ordinal::Period),
+    ("fr", ["lun", "mar", "mer", "jeu", "ven", "sam", "dim"],
+     ["janv", "févr", "mars", "avr", "mai", "juin", "juil", "août", "sept", "oct", "nov", "déc"],
+     Ordinal::None),
+    ("es", ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+     ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic"],
+     Ordinal::None),
+    ("it", ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+     ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+     Ordinal::None),
+    ("nl", ["ma", "di", "wo", "do", "vr", "za", "zo"],
+     ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+     Ordinal::None),
+    ("pt", ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+     ["jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez"],
+     Ordinal::None),
+    ("sv", ["mån", "tis", "ons", "tor", "fre", "lör", "sön"],
+     ["jan", "feb", "mar", "apr", "maj", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+     Ordinal::None),
+];
+
+const WEEKDAYS: [Weekday; 7] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat,
+                                Weekday::Sun];
+
+/// Regions where weeks usually start on Sunday. Everywhere else they start on Monday.
+const SUNDAY_FIRST: [&str; 12] = ["US", "CA", "MX", "BR", "JP", "KR", "TW", "HK", "PH", "IL", "IN", "ZA"];
+
+/// How the day of the month is written in dates.
+#[derive(Clone, Copy)]
+enum Ordinal {
+    /// `18th`
+    English,
+    /// `18.`
+    Period,
+    /// `18`
+    None,
+}
+
+/// The names dates are shown with and the day weeks start on. Entries are still stored under the
+/// English month names whatever the locale.
+pub struct Locale {
+    day_names: Vec<String>,
+    month_names: Vec<String>,
+    ordinal: Ordinal,
+    week_start: u32,
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Sets the locale from the `[locale]` section. Until it's called the locale comes from the
+/// environment alone.
+pub fn init(config: &LocaleConfig) -> Result<(), Error> {
+    let locale = Locale::new(config)?;
+    let _ = LOCALE.set(locale);
+    Ok(())
+}
+
+pub fn get() -> &'static Locale {
+    // Only settings in the config file can be invalid.
+    LOCALE.get_or_init(|| Locale::new(&LocaleConfig::default()).unwrap())
+}
+
+impl Locale {
+    /// Takes the language and region from `$LC_ALL`, `$LC_TIME` or `$LANG` unless they're set in
+    /// `config`.
+    fn new(config: &LocaleConfig) -> Result<Locale, Error> {
+        // eg. `de_AT.UTF-8`.
+        let system = ["LC_ALL", "LC_TIME", "LANG"].iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let system = system.split(['.', '@']).next().unwrap_or("");
+        let (system_language, region) = match system.find('_') {
+            Some(i) => (&system[..i], &system[i + 1..]),
+            None => (system, ""),
+        };
+
+        let (_, days, months, ordinal) = match config.language {
+            Some(ref language) => {
+                *LANGUAGES.iter().find(|&&(name, ..)| name == language).ok_or_else(|| {
+                    let names = LANGUAGES.iter().map(|&(name, ..)| name).collect::<Vec<_>>();
+                    Error::Config(format!("unknown language `{}`, try one of {}", language, names.join(", ")))
+                })?
+            },
+            None => *LANGUAGES.iter().find(|&&(name, ..)| name == system_language).unwrap_or(&LANGUAGES[0]),
+        };
+        let names = |configured: &Option<Vec<String>>, built_in: &[&str], what: &str| match *configured {
+            Some(ref names) if names.len() != built_in.len() => {
+                Err(Error::Config(format!("`{}` needs {} names, not {}", what, built_in.len(), names.len())))
+            },
+            Some(ref names) => Ok(names.clone()),
+            None => Ok(built_in.iter().map(|&name| String::from(name)).collect()),
+        };
+        let week_start = match config.week_start {
+            Some(ref day) => {
+                dates::weekday_from_name(&day.to_lowercase())
+                    .ok_or_else(|| Error::Config(format!("`{}` isn't a day of the week", day)))?
+            },
+            None if SUNDAY_FIRST.contains(&region) => 6,
+            None => 0,
+        };
+        Ok(Locale {
+            day_names: names(&config.day_names, &days, "day_names")?,
+            month_names: names(&config.month_names, &months, "month_names")?,
+            ordinal,
+            week_start,
+        })
+    }
+
+    pub fn day_name(&self, weekday: Weekday) -> &str {
+        &self.day_names[weekday.num_days_from_monday() as usize]
+    }
+
+    pub fn month_name(&self, month0: u32) -> &str {
+        &self.month_names[month0 as usize]
+    }
+
+    /// How many days into the week `weekday` is, counting from the day weeks start on.
+    pub fn day_of_week(&self, weekday: Weekday) -> u32 {
+        (weekday.num_days_from_monday() + 7 - self.week_start) % 7
+    }
+
+    /// The days of the week in the order they're shown, starting with the day weeks start on.
+    pub fn weekdays(&self) -> Vec<Weekday> {
+        (0..7).map(|i| WEEKDAYS[((self.week_start + i) % 7) as usize]).collect()
+    }
+
+    /// The first day of the week `date` is in.
+    pub fn week_start(&self, date: NaiveDate) -> NaiveDate {
+        date - one_day() * self.day_of_week(date.weekday()) as i32
+    }
+
+    /// A date written out, eg. `Sun 18th Oct 2026`.
+    pub fn date_title(&self, date: NaiveDate) -> String {
+        let day = match self.ordinal {
+            Ordinal::English => format!("{}{}", date.day(), english_suffix(date.day())),
+            Ordinal::Period => format!("{}.", date.day()),
+            Ordinal::None => format!("{}", date.day()),
+        };
+        format!("{} {} {} {}", self.day_name(date.weekday()), day, self.month_name(date.month0()), date.year())
+    }
+
+    /// A day of the month with the month's name, eg. `1 Nov`.
+    pub fn day_and_month(&self, date: NaiveDate) -> String {
+        match self.ordinal {
+            Ordinal::Period => format!("{}. {}", date.day(), self.month_name(date.month0())),
+            _ => format!("{} {}", date.day(), self.month_name(date.month0())),
+        }
+    }
+}
+
+fn english_suffix(n: u32) -> &'static str {
+    match (n % 10, (n % 100) / 10) {
+        (1, x) if x != 1 => "st",
+        (2, x) if x != 1 => "nd",
+        (3, x) if x != 1 => "rd",
+        _ => "th",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::date::NaiveDate;
+    use chrono::Weekday;
+
+    use ::config::LocaleConfig;
+    use super::Locale;
+
+    fn locale(language: &str, week_start: &str) -> Locale {
+        Locale::new(&LocaleConfig {
+            language: Some(String::from(language)),
+            week_start: Some(String::from(week_start)),
+            ..LocaleConfig::default()
+        }).unwrap()
+    }
+
+    #[test]
+    fn week_start() {
+        let wednesday = NaiveDate::from_ymd(2017, 5, 31);
+        let sunday_first = locale("en", "Sunday");
+        assert_eq!(sunday_first.weekdays()[0], Weekday::Sun);
+        assert_eq!(sunday_first.day_of_week(Weekday::Wed), 3);
+        assert_eq!(sunday_first.week_start(wednesday), NaiveDate::from_ymd(2017, 5, 28));
+        let monday_first = locale("en", "monday");
+        assert_eq!(monday_first.weekdays()[6], Weekday::Sun);
+        assert_eq!(monday_first.week_start(wednesday), NaiveDate::from_ymd(2017, 5, 29));
+        assert_eq!(monday_first.week_start(NaiveDate::from_ymd(2017, 5, 29)), NaiveDate::from_ymd(2017, 5, 29));
+    }
+
+    #[test]
+    fn names() {
+        let en = locale("en", "monday");
+        assert_eq!(en.date_title(NaiveDate::from_ymd(2017, 5, 31)), "Wed 31st May 2017");
+        assert_eq!(en.date_title(NaiveDate::from_ymd(2017, 5, 11)), "Thu 11th May 2017");
+        assert_eq!(en.date_title(NaiveDate::from_ymd(2017, 5, 22)), "Mon 22nd May 2017");
+        assert_eq!(en.day_and_month(NaiveDate::from_ymd(2017, 11, 1)), "1 Nov");
+        let de = locale("de", "monday");
+        assert_eq!(de.date_title(NaiveDate::from_ymd(2017, 3, 1)), "Mi 1. Mär 2017");
+        assert_eq!(de.day_and_month(NaiveDate::from_ymd(2017, 3, 1)), "1. Mär");
+
+        let days = ["Lu", "Ma", "Me", "Gi", "Ve", "Sa", "Do"];
+        let custom = Locale::new(&LocaleConfig {
+            language: Some(String::from("it")),
+            day_names: Some(days.iter().map(|&day| String::from(day)).collect()),
+            ..LocaleConfig::default()
+        }).unwrap();
+        assert_eq!(custom.day_name(Weekday::Sun), "Do");
+        assert_eq!(custom.month_name(4), "mag");
+    }
+
+    #[test]
+    fn bad_settings() {
+        let config = |language: &str, week_start: &str, day_names: usize| LocaleConfig {
+            language: Some(String::from(language)),
+            week_start: Some(String::from(week_start)),
+            day_names: Some(vec![String::from("x"); day_names]),
+            ..LocaleConfig::default()
+        };
+        assert!(Locale::new(&config("en", "monday", 7)).is_ok());
+        assert!(Locale::new(&config("xx", "monday", 7)).is_err());
+        assert!(Locale::new(&config("en", "someday", 7)).is_err());
+        assert!(Locale::new(&config("en", "monday", 6)).is_err());
+    }
+}
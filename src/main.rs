@@ -2,52 +2,136 @@ extern crate tui;
 extern crate termion;
 extern crate chrono;
 extern crate xdg;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate serde_json;
+extern crate notify;
+extern crate signal_hook;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 
-use std::io;
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use termion::event;
-use termion::input::TermRead;
+use termion::cursor;
+use termion::input::MouseTerminal;
 use termion::screen::AlternateScreen;
 
 use tui::Terminal;
 use tui::backend::TermionBackend;
-use tui::layout::{Group, Size};
+use tui::layout::{Direction, Group, Rect, Size};
 use tui::widgets::Widget;
 
 use chrono::offset::local::Local;
 
 use self::calendar::Calendar;
+use self::config::{Backend, Config};
+use self::database::{Database, Slot};
+use self::editor::Editor;
+use self::error::Error;
+use self::events::{Events, Input};
+use self::keys::Keymap;
+use self::theme::Theme;
+use self::storage::{DirStorage, JournalStorage, MemoryStorage, Storage};
 
+mod config;
 mod editor;
+mod error;
+mod dates;
+mod locale;
+mod quick_add;
+mod keys;
+mod help;
+mod events;
+mod year;
 mod month;
 mod calendar;
 mod week;
+mod day;
 mod database;
+mod storage;
+mod event;
+mod recurrence;
+mod text_editor;
+mod prompt;
+mod search;
+mod status;
+mod theme;
+mod migrate;
+mod ical;
+mod cli;
+mod remind;
+mod agenda;
 
 fn one_day() -> chrono::Duration {
     chrono::Duration::days(1)
 }
 
-fn day_suffix(n: u32) -> &'static str {
-    match (n % 10, (n % 100) / 10) {
-        (1, x) if x != 1 => "st",
-        (2, x) if x != 1 => "nd",
-        (3, x) if x != 1 => "rd",
-        _ => "th",
+/// Where a view last drew each slot, so that a mouse click can be matched to the slot under it.
+#[derive(Default)]
+pub struct HitAreas(RefCell<Vec<(Rect, Slot)>>);
+
+impl HitAreas {
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    pub fn add(&self, rect: Rect, slot: Slot) {
+        self.0.borrow_mut().push((rect, slot));
+    }
+
+    /// The slot drawn at `x`, `y`. Where areas overlap, the first one added wins.
+    pub fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        self.0.borrow().iter()
+            .find(|&&(rect, _)| rect.left() <= x && x < rect.right() && rect.top() <= y && y < rect.bottom())
+            .map(|&(_, slot)| slot)
     }
 }
 
-const DAY_NAMES: [&'static str; 7] = [
-    "Mon",
-    "Tue",
-    "Wed",
-    "Thu",
-    "Fri",
-    "Sat",
-    "Sun",
-];
+/// A directory for a test to work in, removed with everything in it when dropped.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("callus-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The name of an hour in 12-hour time, eg. `12AM` for midnight or `3PM`.
+fn hour_name(hour: u8) -> String {
+    match hour {
+        0 => String::from("12AM"),
+        1..=11 => format!("{}AM", hour),
+        12 => String::from("12PM"),
+        _ => format!("{}PM", hour - 12),
+    }
+}
 
-const MONTH_NAMES: [&'static str; 12] = [
+/// The English month names, which name the month directories of `DirStorage` whatever the
+/// locale. Dates are shown with `locale::get()`.
+const MONTH_NAMES: [&str; 12] = [
     "Jan",
     "Feb",
     "Mar",
@@ -71,8 +155,131 @@ fn main() {
     }
 }
 
-fn run() -> Result<(), io::Error> {
+fn run() -> Result<(), Error> {
+    let config = Config::load()?;
+    locale::init(&config.locale)?;
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(|s| &s[..]) == Some("migrate") {
+        return cli::migrate(&database_path()?, &args[1..]);
+    }
+    let db = Arc::new(open_database(&config)?);
+
+    let res = match args.first().map(|s| &s[..]) {
+        None => run_tui(db.clone(), &config),
+        Some("import") => cli::import(&db, &args[1..]),
+        Some("export") => cli::export(&db, &args[1..]),
+        Some("search") => cli::search(&db, &args[1..]),
+        Some("add") => cli::add(&db, &args[1..]),
+        Some("remind") => cli::remind(&db, &config, &args[1..]),
+        Some("import-dir") => {
+            let location = match args.get(1) {
+                Some(path) => PathBuf::from(path),
+                None => database_path()?,
+            };
+            cli::import_dir(&db, &open_dir_storage(location)?)
+        },
+        Some("agenda") | Some("today") | Some("tomorrow") | Some("week") => cli::agenda(&db, &args[0], &args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            cli::usage();
+            Ok(())
+        },
+        Some(command) => {
+            cli::usage();
+            Err(Error::Usage(format!("unknown command `{}`", command)))
+        },
+    };
+    res?;
+    match db.take_error() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn database_path() -> Result<PathBuf, Error> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("callus")?;
+    match xdg_dirs.find_data_file("db") {
+        Some(path) => Ok(path),
+        None => Ok(xdg_dirs.place_data_file("db")?),
+    }
+}
+
+/// Opens a directory database, bringing it up to the current storage version first.
+fn open_dir_storage(path: PathBuf) -> Result<DirStorage, Error> {
+    let migrated = migrate::migrate_if_needed(&path).map_err(Error::Storage)?;
+    if let Some((plan, backup)) = migrated {
+        eprintln!("callus: migrated {} to storage version {}, renaming {} files. The old version was backed up to {}.",
+                  path.display(), migrate::STORAGE_VERSION, plan.renames.len(), backup.display());
+        for conflict in &plan.conflicts {
+            eprintln!("callus: left {} alone because it clashes with {}", conflict.from.display(), conflict.to.display());
+        }
+    }
+    Ok(DirStorage::new(path))
+}
+
+/// The file used by a single-file backend: the configured path, or `name` in the data directory.
+fn storage_file(config: &Config, name: &str) -> Result<PathBuf, Error> {
+    match config.storage.path {
+        Some(ref path) => Ok(path.clone()),
+        None => Ok(xdg::BaseDirectories::with_prefix("callus")?.place_data_file(name)?),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite_storage(config: &Config) -> Result<Box<dyn Storage>, Error> {
+    let path = storage_file(config, "db.sqlite")?;
+    Ok(Box::new(storage::SqliteStorage::open(&path).map_err(Error::Storage)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite_storage(_config: &Config) -> Result<Box<dyn Storage>, Error> {
+    Err(Error::Config(String::from("the sqlite storage backend needs callus to be built with `--features sqlite`")))
+}
+
+fn open_database(config: &Config) -> Result<Database, Error> {
+    let storage: Box<dyn Storage> = match config.storage.backend {
+        Backend::Dir => Box::new(open_dir_storage(database_path()?)?),
+        Backend::Sqlite => open_sqlite_storage(config)?,
+        Backend::Journal => {
+            let path = storage_file(config, "journal.md")?;
+            Box::new(JournalStorage::open(path).map_err(Error::Storage)?)
+        },
+        Backend::Memory => Box::new(MemoryStorage::new()),
+    };
+    let editor = Editor::new(config.editor.as_ref().map(|s| &s[..]));
+    Ok(Database::new(storage, editor))
+}
+
+/// Runs the calendar, making sure the terminal is put back to normal before a panic's message is
+/// printed. Otherwise the message would be printed to the alternate screen and lost.
+fn run_tui(db: Arc<Database>, config: &Config) -> Result<(), Error> {
+    let message = Arc::new(Mutex::new(None));
+    let hook_message = message.clone();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        *hook_message.lock().unwrap() = Some(info.to_string());
+    }));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| run_calendar(db, config)));
+    panic::set_hook(default_hook);
+    // The cursor is hidden while the calendar is drawn, and isn't shown again if it stopped
+    // early.
+    print!("{}", cursor::Show);
+    let _ = io::stdout().flush();
+    match res {
+        Ok(res) => res,
+        Err(payload) => {
+            if let Some(message) = message.lock().unwrap().take() {
+                eprintln!("callus {}", message);
+            }
+            panic::resume_unwind(payload)
+        },
+    }
+}
+
+fn run_calendar(db: Arc<Database>, config: &Config) -> Result<(), Error> {
+    let keymap = Keymap::new(&config.keys)?;
+    let theme = Theme::new(&config.theme)?;
     let _alt_screen = AlternateScreen::from(io::stdout());
+    let _mouse = MouseTerminal::from(io::stdout());
 
     let backend = TermionBackend::new()?;
     let mut terminal = Terminal::new(backend)?;
@@ -80,40 +287,56 @@ fn run() -> Result<(), io::Error> {
     terminal.clear()?;
     terminal.hide_cursor()?;
 
-    let mut calendar = Calendar::new(Local::now().naive_local());
+    let mut events = Events::new()?;
+    let notifier = events.notifier();
+    remind::spawn(db.clone(), &config.remind, move |message| notifier.send(Input::Reminder(message)));
+
+    let mut calendar = Calendar::new(Local::now().naive_local(), db, config, keymap, theme);
+    let notifier = events.notifier();
+    let _watcher = calendar.watch_storage(move |change| {
+        notifier.send(Input::Changed(change));
+    });
 
     let mut size = terminal.size()?;
-    let mut keys = io::stdin().keys();
     loop {
-        Group::default()
-            .sizes(&[Size::Percent(100)])
-            .render(&mut terminal, &size, |t, chunks| {
-                calendar.render(t, &chunks[0])
-            });
-        terminal.draw()?;
-
-        let c = match keys.next() {
-            Some(c) => c?,
-            None => break,
-        };
-
-        let new_size = terminal.size()?;
-        if new_size != size {
-            size = new_size;
-            terminal.resize(size)?;
+        draw(&mut terminal, &calendar, &size)?;
+        if calendar.show_storage_error() {
+            // The error happened while drawing, so draw again to show it.
+            draw(&mut terminal, &calendar, &size)?;
         }
 
-        let redraw = match c {
-            event::Key::Char('q') => break,
-            event::Key::Left | event::Key::Char('h') => calendar.left(),
-            event::Key::Down | event::Key::Char('j') => calendar.down(),
-            event::Key::Up | event::Key::Char('k') => calendar.up(),
-            event::Key::Right | event::Key::Char('l') => calendar.right(),
-            event::Key::Char('\n') => calendar.enter(),
-            event::Key::Char('>') => calendar.next_view(),
-            event::Key::Char('<') => calendar.prev_view(),
-            _ => false,
+        let redraw = match events.next()? {
+            Input::Key(c) => {
+                calendar.clear_message();
+                match calendar.is_modal() {
+                    true => calendar.modal_key(c),
+                    false => calendar.key(c),
+                }
+            },
+            Input::Mouse(mouse) => {
+                calendar.clear_message();
+                calendar.mouse(mouse)
+            },
+            Input::Resize => {
+                size = terminal.size()?;
+                terminal.resize(size)?;
+                continue;
+            },
+            // Nothing to do but draw again, moving the highlights for today and the current hour.
+            Input::Tick => continue,
+            Input::Changed(change) => {
+                calendar.storage_changed(change);
+                continue;
+            },
+            Input::Reminder(message) => {
+                calendar.notify(message);
+                continue;
+            },
+            Input::End => break,
         };
+        if calendar.should_quit() {
+            break;
+        }
         if redraw {
             terminal.hide_cursor()?;
             terminal.resize(size)?;
@@ -124,3 +347,13 @@ fn run() -> Result<(), io::Error> {
     Ok(())
 }
 
+fn draw(terminal: &mut Terminal<TermionBackend>, calendar: &Calendar, size: &Rect) -> Result<(), io::Error> {
+    Group::default()
+        .direction(Direction::Vertical)
+        .sizes(&[Size::Min(1), Size::Fixed(1)])
+        .render(terminal, size, |t, chunks| {
+            calendar.render(t, &chunks[0]);
+            calendar.status_bar().render(t, &chunks[1]);
+        });
+    terminal.draw()
+}
@@ -0,0 +1,341 @@
+use chrono::offset::local::Local;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use ::storage::dir::sub_dirs;
+use ::MONTH_NAMES;
+
+/// The storage layout written by this version of callus. Version 1 had no `VERSION` file and named
+/// hour entries like `9AM` and `15PM`. Version 2 names them `09.txt` and `15.txt`.
+pub const STORAGE_VERSION: u32 = 2;
+
+const VERSION_FILENAME: &str = "VERSION";
+
+/// A file that needs renaming to bring the database up to date.
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// What migrating a database would do.
+pub struct Plan {
+    pub version: u32,
+    pub renames: Vec<Rename>,
+    /// Files whose new name is taken by another file, eg. both `3PM` and `15PM` exist. These are
+    /// left alone.
+    pub conflicts: Vec<Rename>,
+}
+
+/// The storage version of the database at `location`. A database without a `VERSION` file is
+/// version 1, unless it's empty in which case it can be treated as current.
+pub fn version(location: &Path) -> Result<u32, io::Error> {
+    let path = location.join(VERSION_FILENAME);
+    if !path.exists() {
+        return match has_entries(location) {
+            true => Ok(1),
+            false => Ok(STORAGE_VERSION),
+        };
+    }
+    let mut s = String::new();
+    File::open(&path)?.read_to_string(&mut s)?;
+    s.trim().parse().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: invalid version `{}`", path.display(), s.trim()))
+    })
+}
+
+/// Brings the database at `location` up to the current storage version, backing it up first.
+/// Called whenever the database is opened. Returns the plan if anything was migrated.
+pub fn migrate_if_needed(location: &Path) -> Result<Option<(Plan, PathBuf)>, io::Error> {
+    let version = version(location)?;
+    if version > STORAGE_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "{} uses storage version {} but this callus only understands up to version {}",
+            location.display(), version, STORAGE_VERSION)));
+    }
+    if version == STORAGE_VERSION {
+        if !location.join(VERSION_FILENAME).exists() {
+            write_version(location)?;
+        }
+        return Ok(None);
+    }
+    let plan = plan(location)?;
+    let backup = backup(location)?;
+    apply(location, &plan)?;
+    Ok(Some((plan, backup)))
+}
+
+/// Works out the renames needed to migrate the database, without changing anything.
+pub fn plan(location: &Path) -> Result<Plan, io::Error> {
+    let version = version(location)?;
+    let mut renames = Vec::new();
+    let mut conflicts = Vec::new();
+    if version < 2 {
+        for day_dir in day_dirs(location) {
+            let mut files = fs::read_dir(&day_dir)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>();
+            files.sort();
+            let mut targets = Vec::new();
+            for from in files {
+                let name = from.file_name().unwrap().to_string_lossy().into_owned();
+                let hour = match legacy_hour(&name) {
+                    Some(hour) => hour,
+                    None => continue,
+                };
+                let to = day_dir.join(format!("{:02}.txt", hour));
+                let rename = Rename { from, to };
+                match rename.to.exists() || targets.contains(&rename.to) {
+                    true => conflicts.push(rename),
+                    false => {
+                        targets.push(rename.to.clone());
+                        renames.push(rename);
+                    },
+                }
+            }
+        }
+    }
+    Ok(Plan {
+        version,
+        renames,
+        conflicts,
+    })
+}
+
+pub fn apply(location: &Path, plan: &Plan) -> Result<(), io::Error> {
+    for rename in &plan.renames {
+        fs::rename(&rename.from, &rename.to)?;
+    }
+    write_version(location)
+}
+
+/// Copies the whole database next to itself, as `db.backup-<time>`. Returns the backup's path.
+pub fn backup(location: &Path) -> Result<PathBuf, io::Error> {
+    let mut name = location.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push(format!(".backup-{}", Local::now().naive_local().format("%Y%m%dT%H%M%S")));
+    let backup = location.with_file_name(name);
+    copy_dir(location, &backup)?;
+    Ok(backup)
+}
+
+/// The hour of a version 1 hour entry. Callus wrote the hour in 24-hour time with an `AM` or `PM`
+/// suffix, eg. `15PM`, but files named by hand in 12-hour time such as `3PM` or `12AM` are
+/// understood too.
+fn legacy_hour(name: &str) -> Option<u8> {
+    let (hour, pm) = match (name.strip_suffix("AM"), name.strip_suffix("PM")) {
+        (Some(hour), _) => (hour, false),
+        (_, Some(hour)) => (hour, true),
+        _ => return None,
+    };
+    match (hour.parse::<u8>().ok()?, pm) {
+        (12, false) => Some(0),
+        (hour, true) if hour < 12 => Some(hour + 12),
+        (hour, _) if hour < 24 => Some(hour),
+        _ => None,
+    }
+}
+
+fn write_version(location: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(location)?;
+    let mut f = File::create(location.join(VERSION_FILENAME))?;
+    writeln!(f, "{}", STORAGE_VERSION)
+}
+
+fn has_entries(location: &Path) -> bool {
+    match fs::read_dir(location) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(..) => false,
+    }
+}
+
+/// The `<year>/<month>/<day>` directories of the database.
+fn day_dirs(location: &Path) -> Vec<PathBuf> {
+    let mut days = Vec::new();
+    for year_dir in sub_dirs(location) {
+        for month_dir in sub_dirs(&year_dir) {
+            let is_month = month_dir.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| MONTH_NAMES.contains(&name));
+            if is_month {
+                days.extend(sub_dirs(&month_dir));
+            }
+        }
+    }
+    days
+}
+
+/// Copies a directory and everything in it. Symbolic links are copied as links rather than
+/// followed, so that a link to a directory above can't make the copy go on forever.
+fn copy_dir(from: &Path, to: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        match entry.file_type()? {
+            file_type if file_type.is_symlink() => copy_link(&path, &target)?,
+            file_type if file_type.is_dir() => copy_dir(&path, &target)?,
+            _ => {
+                fs::copy(&path, &target)?;
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> Result<(), io::Error> {
+    ::std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+/// Links aren't copied where making them needs more than the path they point to.
+#[cfg(not(unix))]
+fn copy_link(_from: &Path, _to: &Path) -> Result<(), io::Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use ::TempDir;
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    /// A version 1 database with two hour files for 3PM and an hour file that's already named
+    /// the new way.
+    fn version_1(dir: &TempDir) -> PathBuf {
+        let db = dir.path().join("db");
+        let day = db.join("2017/May/31");
+        write(&day.join("today.txt"), "note");
+        write(&day.join("9AM"), "breakfast");
+        write(&day.join("15PM"), "meeting");
+        write(&day.join("3PM"), "also 3PM");
+        write(&day.join("10AM"), "old 10AM");
+        write(&day.join("10.txt"), "new 10AM");
+        db
+    }
+
+    #[test]
+    fn legacy_hours() {
+        assert_eq!(legacy_hour("0AM"), Some(0));
+        assert_eq!(legacy_hour("9AM"), Some(9));
+        assert_eq!(legacy_hour("15PM"), Some(15));
+        assert_eq!(legacy_hour("23PM"), Some(23));
+
+        assert_eq!(legacy_hour("12AM"), Some(0));
+        assert_eq!(legacy_hour("3PM"), Some(15));
+        assert_eq!(legacy_hour("12PM"), Some(12));
+
+        assert_eq!(legacy_hour("24PM"), None);
+        assert_eq!(legacy_hour("9"), None);
+        assert_eq!(legacy_hour("PM"), None);
+        assert_eq!(legacy_hour("09.txt"), None);
+        assert_eq!(legacy_hour("-1AM"), None);
+    }
+
+    #[test]
+    fn versions() {
+        let dir = TempDir::new("migrate-versions");
+        let db = dir.path().join("db");
+        assert_eq!(version(&db).unwrap(), STORAGE_VERSION);
+        fs::create_dir_all(&db).unwrap();
+        assert_eq!(version(&db).unwrap(), STORAGE_VERSION);
+        write(&db.join("2017/May/31/9AM"), "breakfast");
+        assert_eq!(version(&db).unwrap(), 1);
+
+        write(&db.join(VERSION_FILENAME), "2\n");
+        assert_eq!(version(&db).unwrap(), 2);
+        write(&db.join(VERSION_FILENAME), "");
+        assert!(version(&db).is_err());
+        write(&db.join(VERSION_FILENAME), "two");
+        assert!(version(&db).is_err());
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let dir = TempDir::new("migrate-newer");
+        let db = version_1(&dir);
+        write(&db.join(VERSION_FILENAME), &format!("{}\n", STORAGE_VERSION + 1));
+        assert!(migrate_if_needed(&db).is_err());
+        assert_eq!(read(&db.join("2017/May/31/9AM")), "breakfast");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn plans_renames_and_conflicts() {
+        let dir = TempDir::new("migrate-plan");
+        let db = version_1(&dir);
+        let day = db.join("2017/May/31");
+        let plan = plan(&db).unwrap();
+        assert_eq!(plan.version, 1);
+        let pairs = |renames: &[Rename]| {
+            renames.iter()
+                .map(|rename| (rename.from.strip_prefix(&day).unwrap().to_owned(),
+                               rename.to.strip_prefix(&day).unwrap().to_owned()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pairs(&plan.renames), vec![
+            (PathBuf::from("15PM"), PathBuf::from("15.txt")),
+            (PathBuf::from("9AM"), PathBuf::from("09.txt")),
+        ]);
+        assert_eq!(pairs(&plan.conflicts), vec![
+            (PathBuf::from("10AM"), PathBuf::from("10.txt")),
+            (PathBuf::from("3PM"), PathBuf::from("15.txt")),
+        ]);
+        // Planning doesn't change anything.
+        assert!(day.join("9AM").exists());
+        assert!(!db.join(VERSION_FILENAME).exists());
+    }
+
+    #[test]
+    fn migrates_with_a_backup() {
+        let dir = TempDir::new("migrate-apply");
+        let db = version_1(&dir);
+        let day = db.join("2017/May/31");
+        let (plan, backup) = migrate_if_needed(&db).unwrap().unwrap();
+        assert_eq!(plan.renames.len(), 2);
+
+        assert_eq!(read(&day.join("09.txt")), "breakfast");
+        assert_eq!(read(&day.join("15.txt")), "meeting");
+        assert!(!day.join("9AM").exists());
+        assert_eq!(read(&day.join("3PM")), "also 3PM");
+        assert_eq!(read(&day.join("10AM")), "old 10AM");
+        assert_eq!(read(&day.join("10.txt")), "new 10AM");
+        assert_eq!(read(&day.join("today.txt")), "note");
+        assert_eq!(version(&db).unwrap(), STORAGE_VERSION);
+
+        let backed_up = backup.join("2017/May/31");
+        assert_eq!(read(&backed_up.join("9AM")), "breakfast");
+        assert_eq!(read(&backed_up.join("15PM")), "meeting");
+        assert_eq!(read(&backed_up.join("3PM")), "also 3PM");
+        assert!(!backup.join(VERSION_FILENAME).exists());
+
+        assert!(migrate_if_needed(&db).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backup_copies_links_as_links() {
+        let dir = TempDir::new("migrate-links");
+        let db = version_1(&dir);
+        ::std::os::unix::fs::symlink(&db, db.join("2017/loop")).unwrap();
+        let backup = backup(&db).unwrap();
+        let link = backup.join("2017/loop");
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), db);
+        assert_eq!(read(&backup.join("2017/May/31/9AM")), "breakfast");
+    }
+}
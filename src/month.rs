@@ -1,7 +1,7 @@
 use tui::buffer::Buffer;
 use tui::layout::Rect;
 use tui::widgets::{border, Widget, Block};
-use tui::style::{Color, Modifier, Style};
+use tui::style::Style;
 
 use chrono::naive::date::NaiveDate;
 use chrono::offset::local::Local;
@@ -9,21 +9,27 @@ use chrono::Datelike;
 
 use std::sync::Arc;
 
-use ::{DAY_NAMES, MONTH_NAMES, one_day};
-use ::database::Database;
+use ::{HitAreas, one_day};
+use ::locale;
+use ::database::{Database, Slot};
+use ::theme::{self, Theme};
 
 pub struct MonthView {
     selected_date: NaiveDate,
     db: Arc<Database>,
+    theme: Theme,
+    hit_areas: HitAreas,
 }
 
 impl MonthView {
-    pub fn new(db: Arc<Database>, date: NaiveDate) -> MonthView {
+    pub fn new(db: Arc<Database>, theme: Theme, date: NaiveDate) -> MonthView {
         //let cur_day = date.weekday().num_days_from_monday();
         //let cur_week = (date.day0() + cur_day) / 7;
         MonthView {
             selected_date: date,
-            db: db,
+            db,
+            theme,
+            hit_areas: HitAreas::default(),
         }
     }
 
@@ -55,24 +61,34 @@ impl MonthView {
         false
     }
 
-    pub fn enter(&mut self) -> bool {
-        let date = self.selected_date;
-        self.db.edit_day(date);
-        true
+    pub fn selected_slot(&self) -> Slot {
+        Slot::Day(self.selected_date)
+    }
+
+    /// The day drawn at `x`, `y` as of the last draw.
+    pub fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        self.hit_areas.slot_at(x, y)
     }
 
     pub fn draw(&self, area: &Rect, buffer: &mut Buffer) {
-        let title = format!("{} {}", MONTH_NAMES[self.selected_date.month0() as usize], self.selected_date.year());
-        buffer.set_string((area.width - title.len() as u16) / 2, 0, &title, &Style::default().fg(Color::Yellow).modifier(Modifier::Bold));
+        self.hit_areas.clear();
+        let locale = locale::get();
+        let title = format!("{} {}", locale.month_name(self.selected_date.month0()), self.selected_date.year());
+        buffer.set_string((area.width - title.chars().count() as u16) / 2, 0, &title, &self.theme.title.style());
 
         let first_day_of_month = self.selected_date - (one_day() * self.selected_date.day0() as i32);
-        let first_day = first_day_of_month - (one_day() * first_day_of_month.weekday().num_days_from_monday() as i32);
+        let first_day = locale.week_start(first_day_of_month);
+        self.db.prefetch(first_day, first_day + one_day() * 41);
 
         let w = area.width - 1;
         let h = area.height - 4;
-        for day_num in 0..7 {
+        for (day_num, weekday) in (0..7).zip(locale.weekdays()) {
             let x = 1 + (1 + day_num * 2) * w / 14;
-            buffer.set_string(x, 2, DAY_NAMES[day_num as usize], &Style::default().modifier(Modifier::Bold));
+            let mut header_style = self.theme.header.style();
+            if weekday.num_days_from_monday() >= 5 {
+                header_style = self.theme.weekend.apply(header_style);
+            }
+            buffer.set_string(x, 2, locale.day_name(weekday), &header_style);
             for week_num in 0..6 {
                 let rect = Rect {
                     x: day_num * w / 7,
@@ -83,55 +99,48 @@ impl MonthView {
 
                 let today = Local::now().naive_local().date();
                 let day = first_day + one_day() * (7 * week_num + day_num) as i32;
+                self.hit_areas.add(rect, Slot::Day(day));
 
                 let day_of_month = match day.month0() == self.selected_date.month0() {
                     true => format!("{}", day.day()),
-                    false => format!("{} {}", day.day(), MONTH_NAMES[day.month0() as usize]),
-                };
-                let fg = match day == today {
-                    true => Some(Color::Yellow),
-                    false => match day == self.selected_date {
-                        true => Some(Color::Black),
-                        false => match day.month0() == self.selected_date.month0() {
-                            true => None,
-                            false => Some(Color::Red),
-                        },
-                    }
-                };
-                let bg = match day == self.selected_date {
-                    true => Some(Color::Red),
-                    false => None,
+                    false => locale.day_and_month(day),
                 };
+                let selected = day == self.selected_date;
                 let number_style = {
                     let mut number_style = Style::default();
                     if day.month0() == self.selected_date.month0() {
-                        number_style = number_style.modifier(Modifier::Bold);
+                        number_style = theme::bold(number_style);
+                    } else {
+                        number_style = self.theme.other_month.apply(number_style);
                     }
-                    if let Some(fg) = fg {
-                        number_style = number_style.fg(fg);
+                    if day.weekday().num_days_from_monday() >= 5 {
+                        number_style = self.theme.weekend.apply(number_style);
                     }
-                    if let Some(bg) = bg {
-                        number_style = number_style.bg(bg);
+                    if selected {
+                        number_style = self.theme.selected.apply(number_style);
                     }
-                    number_style
-                };
-                let line_style = {
-                    let mut line_style = Style::default();
-                    if let Some(bg) = bg {
-                        line_style = line_style.bg(bg);
+                    if day == today {
+                        number_style = self.theme.today.apply(number_style);
                     }
-                    line_style
+                    number_style
                 };
 
-                buffer.set_string(rect.x + 1, rect.y + 1, &day_of_month, &number_style);
-                let block = Block::default().borders(border::ALL).style(line_style);
+                let block = Block::default().borders(border::ALL);
                 block.draw(&rect, buffer);
+                if selected {
+                    theme::fill(&block.inner(&rect), buffer, &self.theme.selected.style());
+                }
+                buffer.set_string(rect.x + 1, rect.y + 1, &day_of_month, &number_style);
 
-                let mut style = Style::default();
-                if day == self.selected_date {
-                    style = style.fg(Color::Black).bg(Color::Red);
+                let mut style = self.theme.entry.style();
+                if selected {
+                    style = self.theme.selected.apply(style);
                 }
                 let mut summary = self.db.get_day(day);
+                for event in self.db.get_events(day) {
+                    summary.push_str(&event.hour_label(day, 0));
+                    summary.push('\n');
+                }
                 for hour in 0..24 {
                     summary.push_str(&self.db.get_hour(day, hour));
                 }
@@ -143,3 +152,56 @@ impl MonthView {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use tui::buffer::Buffer;
+    use tui::layout::Rect;
+
+    use chrono::naive::date::NaiveDate;
+
+    use std::sync::Arc;
+
+    use super::MonthView;
+    use ::locale;
+    use ::config::ThemeConfig;
+    use ::database::{Database, Slot};
+    use ::editor::Editor;
+    use ::storage::memory::MemoryStorage;
+    use ::theme::Theme;
+
+    /// Where `text` was drawn.
+    fn find(buffer: &Buffer, text: &str) -> Option<(u16, u16)> {
+        let area = *buffer.area();
+        (area.top()..area.bottom()).flat_map(|y| (area.left()..area.right()).map(move |x| (x, y)))
+            .find(|&(x, y)| {
+                text.chars().enumerate().all(|(i, c)| {
+                    x + (i as u16) < area.right() && buffer.get(x + i as u16, y).symbol == c.to_string()
+                })
+            })
+    }
+
+    #[test]
+    fn grid() {
+        let db = Arc::new(Database::new(Box::new(MemoryStorage::new()), Editor::new(None)));
+        let selected = NaiveDate::from_ymd(2017, 5, 17);
+        db.set(Slot::Day(selected), "dentist").unwrap();
+        db.set(Slot::Hour(NaiveDate::from_ymd(2017, 5, 31), 9), "standup").unwrap();
+        let view = MonthView::new(db, Theme::new(&ThemeConfig::default()).unwrap(), selected);
+        let area = Rect { x: 0, y: 0, width: 120, height: 40 };
+        let mut buffer = Buffer::empty(area);
+        view.draw(&area, &mut buffer);
+
+        let locale = locale::get();
+        assert!(find(&buffer, &format!("{} 2017", locale.month_name(4))).is_some());
+        let first_day = locale.week_start(NaiveDate::from_ymd(2017, 5, 1));
+        assert_eq!(view.slot_at(1, 4), Some(Slot::Day(first_day)));
+        assert_eq!(view.slot_at(area.width - 2, area.height - 2),
+                   Some(Slot::Day(first_day + ::one_day() * 41)));
+
+        let (x, y) = find(&buffer, "dentist").unwrap();
+        assert_eq!(view.slot_at(x, y), Some(Slot::Day(selected)));
+        let (x, y) = find(&buffer, "standup").unwrap();
+        assert_eq!(view.slot_at(x, y), Some(Slot::Day(NaiveDate::from_ymd(2017, 5, 31))));
+    }
+}
@@ -0,0 +1,90 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+
+use termion::event::Key;
+
+pub enum PromptResult {
+    Continue,
+    Submit(String),
+    Cancel,
+}
+
+/// A single line of text input shown at the bottom of the screen, eg. for entering a file name.
+pub struct Prompt {
+    /// Shown before the input, eg. `Search: `.
+    prefix: String,
+    input: Vec<char>,
+    cursor: usize,
+}
+
+impl Prompt {
+    pub fn new(label: &str) -> Prompt {
+        Prompt {
+            prefix: format!("{}: ", label),
+            input: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// A vi-style `:` command line.
+    pub fn command() -> Prompt {
+        Prompt {
+            prefix: String::from(":"),
+            input: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn key(&mut self, key: Key) -> PromptResult {
+        match key {
+            Key::Esc => return PromptResult::Cancel,
+            Key::Char('\n') => return PromptResult::Submit(self.input.iter().collect()),
+            Key::Left if self.cursor > 0 => self.cursor -= 1,
+            Key::Right if self.cursor < self.input.len() => self.cursor += 1,
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.input.len(),
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            },
+            Key::Backspace if self.input.is_empty() => return PromptResult::Cancel,
+            Key::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            },
+            Key::Ctrl('u') => {
+                self.input.drain(..self.cursor);
+                self.cursor = 0;
+            },
+            Key::Char(c) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            },
+            _ => (),
+        }
+        PromptResult::Continue
+    }
+
+    /// Draws the prompt on the first line of `area`.
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        for x in area.left()..area.right() {
+            buffer.get_mut(x, area.y).reset();
+        }
+        let label = &self.prefix;
+        let input = self.input.iter().collect::<String>();
+        let width = area.width as usize;
+        let label_len = label.chars().count();
+        // Scroll the input horizontally so that the cursor stays on screen.
+        let skip = (label_len + self.cursor + 1).saturating_sub(width);
+        let line = label.chars().chain(input.chars()).skip(skip).collect::<String>();
+        buffer.set_stringn(area.x, area.y, &line, width, &Style::default().modifier(Modifier::Bold));
+        let cursor_x = area.x + (label_len + self.cursor - skip) as u16;
+        if cursor_x < area.right() {
+            buffer.get_mut(cursor_x, area.y).set_modifier(Modifier::Invert);
+        }
+    }
+}
+
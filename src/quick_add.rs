@@ -0,0 +1,233 @@
+use chrono::naive::date::NaiveDate;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::naive::time::NaiveTime;
+use chrono::{Duration, Timelike};
+
+use ::database::Slot;
+use ::dates;
+
+/// An entry typed as one line, eg. `lunch with Sam tomorrow 1pm for 90m`, split into where it
+/// goes and what it says.
+pub struct QuickAdd {
+    pub slot: Slot,
+    pub text: String,
+}
+
+/// Picks the date, time and duration out of `input`, relative to `now`. Whatever is left is the
+/// entry's text. Without a date the entry goes on today, and without a time it's added to the
+/// day's note. The hour boxes can't show minutes or durations, so if either is given the text
+/// starts with the time range, eg. `13:30-15:00 lunch with Sam`.
+pub fn parse(input: &str, now: NaiveDateTime) -> Result<QuickAdd, String> {
+    let words = input.split_whitespace().collect::<Vec<_>>();
+    let lower = words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>();
+    let mut date = None;
+    let mut time = None;
+    let mut duration = None;
+    let mut text = Vec::new();
+
+    let mut i = 0;
+    while i < words.len() {
+        // "at 1pm", "for 2h" and "on friday" lose their first word along with the time, duration
+        // or date.
+        let word = &lower[i][..];
+        if time.is_none() {
+            let start = if word == "at" { 1 } else { 0 };
+            if let Some((t, len)) = parse_time(&lower[i + start..]) {
+                time = Some(t);
+                i += start + len;
+                continue;
+            }
+        }
+        if duration.is_none() && word == "for" {
+            if let Some((d, len)) = parse_duration(&lower[i + 1..]) {
+                duration = Some(d);
+                i += 1 + len;
+                continue;
+            }
+        }
+        if date.is_none() {
+            let next_is_weekday = lower.get(i + 1).is_some_and(|next| dates::weekday_from_name(next).is_some());
+            let start = match word {
+                "on" => 1,
+                "this" if next_is_weekday => 1,
+                _ => 0,
+            };
+            if let Some((d, len)) = parse_date(&lower[i + start..], now.date(), start == 1) {
+                date = Some(d);
+                i += start + len;
+                continue;
+            }
+        }
+        text.push(words[i]);
+        i += 1;
+    }
+
+    let mut text = text.join(" ");
+    if text.is_empty() {
+        return Err(String::from("nothing to add"));
+    }
+    let date = date.unwrap_or_else(|| now.date());
+    let slot = match time {
+        Some(time) => {
+            if time.minute() != 0 || duration.is_some() {
+                let end = time + duration.unwrap_or_else(|| Duration::hours(1));
+                text = format!("{}-{} {}", time.format("%H:%M"), end.format("%H:%M"), text);
+            }
+            Slot::Hour(date, time.hour() as u8)
+        },
+        None => Slot::Day(date),
+    };
+    Ok(QuickAdd {
+        slot,
+        text,
+    })
+}
+
+/// Reads a date from the start of `words`, returning it and how many words it took. Longer dates
+/// are tried first so that `next friday` isn't read as just `friday`.
+///
+/// Words that only look like dates are left in the text: offsets such as `+3`, and weekdays on
+/// their own unless they come after `on` or `this` (`after_on`), or are written in full at the
+/// end of the input or before the time, so that `lunch in the sun` stays as it is.
+fn parse_date(words: &[String], today: NaiveDate, after_on: bool) -> Option<(NaiveDate, usize)> {
+    if words.first().is_some_and(|word| word.starts_with('+') || word.starts_with('-')) {
+        return None;
+    }
+    for len in (1..words.len().min(3) + 1).rev() {
+        if len == 1 && !after_on && dates::weekday_from_name(&words[0]).is_some() {
+            let rest = &words[1..];
+            let ends_input = rest.is_empty() || rest[0] == "at" || rest[0] == "for" || parse_time(rest).is_some();
+            if !dates::WEEKDAY_NAMES.contains(&&words[0][..]) || !ends_input {
+                continue;
+            }
+        }
+        if let Some(date) = dates::parse(&words[..len].join(" "), today) {
+            return Some((date, len));
+        }
+    }
+    None
+}
+
+/// Reads a time such as `1pm`, `1:30 pm`, `13:00`, `noon` or `midnight` from the start of
+/// `words`, returning it and how many words it took.
+fn parse_time(words: &[String]) -> Option<(NaiveTime, usize)> {
+    let word = words.first()?;
+    match &word[..] {
+        "noon" | "midday" => return Some((NaiveTime::from_hms(12, 0, 0), 1)),
+        "midnight" => return Some((NaiveTime::from_hms(0, 0, 0), 1)),
+        _ => (),
+    }
+    let (clock, suffix, len) = match words.get(1).map(|w| &w[..]) {
+        Some(suffix @ "am") | Some(suffix @ "pm") => (&word[..], suffix, 2),
+        _ if word.ends_with("am") || word.ends_with("pm") => (&word[..word.len() - 2], &word[word.len() - 2..], 1),
+        _ if word.contains(':') => (&word[..], "", 1),
+        _ => return None,
+    };
+    let (hour, minute) = match clock.find(':') {
+        Some(i) => (clock[..i].parse::<u32>().ok()?, clock[i + 1..].parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    let hour = match suffix {
+        "" if hour < 24 => hour,
+        "am" | "pm" if (1..13).contains(&hour) => hour % 12 + if suffix == "pm" { 12 } else { 0 },
+        _ => return None,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0).map(|time| (time, len))
+}
+
+/// Reads a duration such as `2h`, `90m`, `1h30m`, `2 hours` or `45 minutes` from the start of
+/// `words`, returning it and how many words it took.
+fn parse_duration(words: &[String]) -> Option<(Duration, usize)> {
+    let word = words.first()?;
+    let (minutes, len) = match word.parse::<i64>() {
+        Ok(n) => (unit_minutes(n, words.get(1)?)?, 2),
+        Err(..) => {
+            let mut total = 0i64;
+            let mut rest = &word[..];
+            while !rest.is_empty() {
+                let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+                let n = rest[..digits].parse().ok()?;
+                let unit_len = rest[digits..].find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len() - digits);
+                total = total.checked_add(unit_minutes(n, &rest[digits..digits + unit_len])?)?;
+                rest = &rest[digits + unit_len..];
+            }
+            (total, 1)
+        },
+    };
+    match minutes {
+        1..=MAX_DURATION_MINUTES => Some((Duration::minutes(minutes), len)),
+        _ => None,
+    }
+}
+
+/// The longest duration that can be added, since entries only show the times within a day.
+const MAX_DURATION_MINUTES: i64 = 24 * 60;
+
+fn unit_minutes(n: i64, unit: &str) -> Option<i64> {
+    match unit {
+        "h" | "hr" | "hrs" | "hour" | "hours" => n.checked_mul(60),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2017, m, d)
+    }
+
+    /// Adds `input` on Wednesday the 31st of May 2017 at 10AM.
+    fn add(input: &str) -> (Slot, String) {
+        let quick_add = parse(input, date(5, 31).and_hms(10, 0, 0)).unwrap();
+        (quick_add.slot, quick_add.text)
+    }
+
+    #[test]
+    fn dates_and_times() {
+        assert_eq!(add("lunch with Sam tomorrow 1pm"), (Slot::Hour(date(6, 1), 13), String::from("lunch with Sam")));
+        assert_eq!(add("call Mum"), (Slot::Day(date(5, 31)), String::from("call Mum")));
+        assert_eq!(add("dentist friday 3pm"), (Slot::Hour(date(6, 2), 15), String::from("dentist")));
+        assert_eq!(add("standup on mon at 9:30 am"),
+                   (Slot::Hour(date(6, 5), 9), String::from("09:30-10:30 standup")));
+        assert_eq!(add("party next fri 8 pm"), (Slot::Hour(date(6, 2), 20), String::from("party")));
+        assert_eq!(add("gym this thu"), (Slot::Day(date(6, 1)), String::from("gym")));
+        assert_eq!(add("bins 2017-06-05 midnight"), (Slot::Hour(date(6, 5), 0), String::from("bins")));
+        assert_eq!(add("Lunch NOON"), (Slot::Hour(date(5, 31), 12), String::from("Lunch")));
+    }
+
+    #[test]
+    fn words_that_look_like_dates() {
+        assert_eq!(add("lunch in the sun"), (Slot::Day(date(5, 31)), String::from("lunch in the sun")));
+        assert_eq!(add("do this tomorrow"), (Slot::Day(date(6, 1)), String::from("do this")));
+        assert_eq!(add("score +3 points"), (Slot::Day(date(5, 31)), String::from("score +3 points")));
+        assert_eq!(add("wed wedding"), (Slot::Day(date(5, 31)), String::from("wed wedding")));
+        assert_eq!(add("sat nav fix"), (Slot::Day(date(5, 31)), String::from("sat nav fix")));
+        assert_eq!(add("read friday notes"), (Slot::Day(date(5, 31)), String::from("read friday notes")));
+        assert_eq!(add("read notes friday"), (Slot::Day(date(6, 2)), String::from("read notes")));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(add("lunch 1pm for 90m"), (Slot::Hour(date(5, 31), 13), String::from("13:00-14:30 lunch")));
+        assert_eq!(add("walk at 2pm for 1h30m"), (Slot::Hour(date(5, 31), 14), String::from("14:00-15:30 walk")));
+        assert_eq!(add("nap 3pm for 45 minutes"), (Slot::Hour(date(5, 31), 15), String::from("15:00-15:45 nap")));
+        assert_eq!(add("exam 9am for 24h"), (Slot::Hour(date(5, 31), 9), String::from("09:00-09:00 exam")));
+
+        assert_eq!(add("trip 9am for 25h"), (Slot::Hour(date(5, 31), 9), String::from("trip for 25h")));
+        assert_eq!(add("trip 9am for 0m"), (Slot::Hour(date(5, 31), 9), String::from("trip for 0m")));
+        assert_eq!(add("trip 9am for 9223372036854775807h"),
+                   (Slot::Hour(date(5, 31), 9), String::from("trip for 9223372036854775807h")));
+        assert_eq!(add("trip 9am for 9223372036854775807m1m"),
+                   (Slot::Hour(date(5, 31), 9), String::from("trip for 9223372036854775807m1m")));
+        assert_eq!(add("trip for 2h"), (Slot::Day(date(5, 31)), String::from("trip")));
+    }
+
+    #[test]
+    fn nothing_to_add() {
+        assert!(parse("tomorrow 3pm", date(5, 31).and_hms(10, 0, 0)).is_err());
+        assert!(parse("   ", date(5, 31).and_hms(10, 0, 0)).is_err());
+    }
+}
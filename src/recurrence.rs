@@ -0,0 +1,424 @@
+use chrono::naive::date::NaiveDate;
+use chrono::{Datelike, Duration};
+
+use std::fmt;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The FREQ, INTERVAL, BYDAY, COUNT and UNTIL parts of an RFC 5545 recurrence rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RRule {
+    pub freq: Freq,
+    pub interval: u32,
+    /// Weekdays as days from Monday, each optionally limited to the nth one in the month.
+    pub by_day: Vec<(Option<i32>, u32)>,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDate>,
+}
+
+impl RRule {
+    pub fn parse(s: &str) -> Result<RRule, String> {
+        let mut freq = None;
+        let mut rule = RRule {
+            freq: Freq::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            count: None,
+            until: None,
+        };
+        for part in s.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+            let mut kv = part.splitn(2, '=');
+            let name = kv.next().unwrap_or("").to_uppercase();
+            let value = kv.next().unwrap_or("").trim().to_uppercase();
+            let invalid = || format!("invalid {} `{}`", name, value);
+            match &name[..] {
+                "FREQ" => freq = Some(match &value[..] {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    "YEARLY" => Freq::Yearly,
+                    _ => return Err(invalid()),
+                }),
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|n| *n > 0).ok_or_else(invalid)?,
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| invalid())?),
+                "UNTIL" => {
+                    let date = value.get(..8).ok_or_else(invalid)?;
+                    rule.until = Some(NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| invalid())?);
+                },
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let split = day.len().checked_sub(2).ok_or_else(invalid)?;
+                        let weekday = day.get(split..).and_then(weekday_from_str).ok_or_else(invalid)?;
+                        let n = match day.get(..split).ok_or_else(invalid)? {
+                            "" => None,
+                            // RFC 5545 ordinals go up to 53, the most weeks in a year.
+                            n => Some(n.trim_start_matches('+').parse::<i32>().ok()
+                                .filter(|n| (1..=53).contains(&n.unsigned_abs()))
+                                .ok_or_else(invalid)?),
+                        };
+                        rule.by_day.push((n, weekday));
+                    }
+                },
+                "WKST" => (),
+                _ => return Err(format!("unsupported rule part `{}`", name)),
+            }
+        }
+        match freq {
+            Some(freq) => rule.freq = freq,
+            None => return Err(String::from("rule has no FREQ")),
+        }
+        Ok(rule)
+    }
+
+}
+
+impl fmt::Display for RRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = format!("FREQ={}", match self.freq {
+            Freq::Daily => "DAILY",
+            Freq::Weekly => "WEEKLY",
+            Freq::Monthly => "MONTHLY",
+            Freq::Yearly => "YEARLY",
+        });
+        if self.interval != 1 {
+            s.push_str(&format!(";INTERVAL={}", self.interval));
+        }
+        if !self.by_day.is_empty() {
+            let days = self.by_day.iter().map(|&(n, weekday)| {
+                let name = WEEKDAYS[weekday as usize];
+                match n {
+                    Some(n) => format!("{}{}", n, name),
+                    None => String::from(name),
+                }
+            }).collect::<Vec<_>>();
+            s.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        if let Some(count) = self.count {
+            s.push_str(&format!(";COUNT={}", count));
+        }
+        if let Some(until) = self.until {
+            s.push_str(&format!(";UNTIL={}", until.format("%Y%m%d")));
+        }
+        write!(f, "{}", s)
+    }
+}
+
+impl RRule {
+    /// Whether the rule, first occurring on `start`, occurs on `day`.
+    pub fn occurs_on(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        if !self.matches(start, day) {
+            return false;
+        }
+        match self.count {
+            Some(count) => {
+                let mut seen = 0;
+                let mut date = start;
+                while date < day {
+                    if self.matches(start, date) {
+                        seen += 1;
+                        if seen >= count {
+                            return false;
+                        }
+                    }
+                    date = date + Duration::days(1);
+                }
+                true
+            },
+            None => true,
+        }
+    }
+
+    /// Whether `day` fits the rule, ignoring COUNT.
+    fn matches(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        if day < start || self.until.is_some_and(|until| day > until) {
+            return false;
+        }
+        let interval = self.interval as i64;
+        let weekday = day.weekday().num_days_from_monday();
+        let months = |date: NaiveDate| date.year() as i64 * 12 + date.month0() as i64;
+        match self.freq {
+            Freq::Daily => {
+                (day.num_days_from_ce() - start.num_days_from_ce()) as i64 % interval == 0
+                    && (self.by_day.is_empty() || self.by_day.iter().any(|&(_, d)| d == weekday))
+            },
+            Freq::Weekly => {
+                let monday = |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                let weeks = (monday(day).num_days_from_ce() - monday(start).num_days_from_ce()) as i64 / 7;
+                weeks % interval == 0 && match self.by_day.is_empty() {
+                    true => weekday == start.weekday().num_days_from_monday(),
+                    false => self.by_day.iter().any(|&(_, d)| d == weekday),
+                }
+            },
+            Freq::Monthly => {
+                (months(day) - months(start)) % interval == 0 && self.matches_in_month(start, day)
+            },
+            Freq::Yearly => {
+                (day.year() - start.year()) as i64 % interval == 0
+                    && day.month() == start.month()
+                    && self.matches_in_month(start, day)
+            },
+        }
+    }
+
+    fn matches_in_month(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        if self.by_day.is_empty() {
+            return day.day() == start.day();
+        }
+        let weekday = day.weekday().num_days_from_monday();
+        self.by_day.iter().any(|&(n, d)| match n {
+            None => d == weekday,
+            Some(n) => nth_weekday_of_month(day.year(), day.month(), d, n) == Some(day),
+        })
+    }
+}
+
+/// An entry that repeats according to a rule, stored once rather than in every slot it appears.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recurring {
+    pub id: String,
+    pub rule: RRule,
+    pub start: NaiveDate,
+    /// The hour the entry appears at, or `None` if it belongs with the day's note.
+    pub hour: Option<u8>,
+    /// Days on which the entry would have occurred but has been skipped.
+    pub exceptions: Vec<NaiveDate>,
+    pub text: String,
+}
+
+impl Recurring {
+    /// A weekly entry with no text, as a template for the user to fill in.
+    pub fn new(id: String, start: NaiveDate, hour: Option<u8>) -> Recurring {
+        Recurring {
+            id,
+            rule: RRule {
+                freq: Freq::Weekly,
+                interval: 1,
+                by_day: Vec::new(),
+                count: None,
+                until: None,
+            },
+            start,
+            hour,
+            exceptions: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    pub fn occurs_on(&self, day: NaiveDate) -> bool {
+        !self.exceptions.contains(&day) && self.rule.occurs_on(self.start, day)
+    }
+
+    /// Parses the text written by `to_text`.
+    pub fn parse(id: &str, text: &str) -> Result<Recurring, String> {
+        let mut rule = None;
+        let mut start = None;
+        let mut hour = None;
+        let mut exceptions = Vec::new();
+        let mut lines = text.lines();
+        for line in &mut lines {
+            if line.trim().is_empty() {
+                break;
+            }
+            let (name, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(format!("expected `field: value`, got `{}`", line)),
+            };
+            match &name.to_lowercase()[..] {
+                "rrule" => rule = Some(RRule::parse(value)?),
+                "start" => start = Some(parse_date(value)?),
+                "hour" if value.is_empty() => (),
+                "hour" => hour = Some(value.parse::<u8>().ok().filter(|h| *h < 24).ok_or_else(|| {
+                    format!("invalid hour `{}`, expected 0 to 23", value)
+                })?),
+                "except" => {
+                    for date in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                        exceptions.push(parse_date(date)?);
+                    }
+                },
+                _ => return Err(format!("unknown field `{}`", name)),
+            }
+        }
+        let text = lines.collect::<Vec<_>>().join("\n");
+
+        match (rule, start) {
+            (Some(rule), Some(start)) => Ok(Recurring {
+                id: String::from(id),
+                rule,
+                start,
+                hour,
+                exceptions,
+                text,
+            }),
+            (None, _) => Err(String::from("recurring entry has no rrule")),
+            (_, None) => Err(String::from("recurring entry has no start date")),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("rrule: {}\n", self.rule));
+        text.push_str(&format!("start: {}\n", self.start.format(DATE_FORMAT)));
+        text.push_str(&format!("hour: {}\n", self.hour.map(|h| h.to_string()).unwrap_or_default()));
+        let exceptions = self.exceptions.iter().map(|d| d.format(DATE_FORMAT).to_string()).collect::<Vec<_>>();
+        text.push_str(&format!("except: {}\n", exceptions.join(", ")));
+        text.push('\n');
+        text.push_str(&self.text);
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, DATE_FORMAT).map_err(|e| {
+        format!("invalid date `{}` ({}), expected eg. `2017-05-31`", s, e)
+    })
+}
+
+const WEEKDAYS: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+
+/// Parses a two-letter iCalendar weekday into its number of days from Monday.
+pub fn weekday_from_str(s: &str) -> Option<u32> {
+    WEEKDAYS.iter().position(|d| *d == s).map(|i| i as u32)
+}
+
+/// The `n`th `weekday` of a month, counting from the end of the month if `n` is negative.
+pub fn nth_weekday_of_month(year: i32, month: u32, weekday: u32, n: i32) -> Option<NaiveDate> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next_month = match month {
+        12 => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+        _ => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+    };
+    let weeks = (n as i64).abs() - 1;
+    let date = if n > 0 {
+        let offset = (7 + weekday - first.weekday().num_days_from_monday()) % 7;
+        first.checked_add_signed(Duration::days(offset as i64 + 7 * weeks))?
+    } else {
+        let last = next_month.pred_opt()?;
+        let offset = (7 + last.weekday().num_days_from_monday() - weekday) % 7;
+        last.checked_sub_signed(Duration::days(offset as i64 + 7 * weeks))?
+    };
+    match date.month() == month && date.year() == year {
+        true => Some(date),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    fn days(rule: &str, start: NaiveDate, until: NaiveDate) -> Vec<NaiveDate> {
+        let rule = RRule::parse(rule).unwrap();
+        let mut days = Vec::new();
+        let mut day = start;
+        while day <= until {
+            if rule.occurs_on(start, day) {
+                days.push(day);
+            }
+            day = day + Duration::days(1);
+        }
+        days
+    }
+
+    #[test]
+    fn parse() {
+        let rule = RRule::parse("freq=monthly; interval=2;BYDAY=+1MO,-1FR;COUNT=5;UNTIL=20171231T000000Z").unwrap();
+        assert_eq!(rule, RRule {
+            freq: Freq::Monthly,
+            interval: 2,
+            by_day: vec![(Some(1), 0), (Some(-1), 4)],
+            count: Some(5),
+            until: Some(date(2017, 12, 31)),
+        });
+        assert_eq!(rule.to_string(), "FREQ=MONTHLY;INTERVAL=2;BYDAY=1MO,-1FR;COUNT=5;UNTIL=20171231");
+        assert_eq!(RRule::parse(&rule.to_string()), Ok(rule));
+
+        assert!(RRule::parse("INTERVAL=2").is_err());
+        assert!(RRule::parse("FREQ=HOURLY").is_err());
+        assert!(RRule::parse("FREQ=WEEKLY;INTERVAL=0").is_err());
+        assert!(RRule::parse("FREQ=WEEKLY;BYDAY=X").is_err());
+        assert!(RRule::parse("FREQ=WEEKLY;BYDAY=éX").is_err());
+        assert!(RRule::parse("FREQ=WEEKLY;BYSETPOS=1").is_err());
+    }
+
+    #[test]
+    fn daily_and_weekly() {
+        assert_eq!(days("FREQ=DAILY;INTERVAL=3", date(2017, 5, 1), date(2017, 5, 10)),
+                   vec![date(2017, 5, 1), date(2017, 5, 4), date(2017, 5, 7), date(2017, 5, 10)]);
+        assert_eq!(days("FREQ=WEEKLY", date(2017, 5, 3), date(2017, 5, 20)),
+                   vec![date(2017, 5, 3), date(2017, 5, 10), date(2017, 5, 17)]);
+        assert_eq!(days("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR", date(2017, 5, 1), date(2017, 5, 19)),
+                   vec![date(2017, 5, 1), date(2017, 5, 5), date(2017, 5, 15), date(2017, 5, 19)]);
+    }
+
+    #[test]
+    fn monthly_and_yearly() {
+        assert_eq!(days("FREQ=MONTHLY", date(2017, 1, 31), date(2017, 5, 31)),
+                   vec![date(2017, 1, 31), date(2017, 3, 31), date(2017, 5, 31)]);
+        assert_eq!(days("FREQ=MONTHLY;BYDAY=-1FR", date(2017, 5, 1), date(2017, 7, 31)),
+                   vec![date(2017, 5, 26), date(2017, 6, 30), date(2017, 7, 28)]);
+        assert_eq!(days("FREQ=YEARLY;BYDAY=4TH", date(2017, 11, 1), date(2019, 12, 31)),
+                   vec![date(2017, 11, 23), date(2018, 11, 22), date(2019, 11, 28)]);
+    }
+
+    #[test]
+    fn count_and_until() {
+        assert_eq!(days("FREQ=WEEKLY;BYDAY=TU,TH;COUNT=3", date(2017, 5, 2), date(2017, 5, 31)),
+                   vec![date(2017, 5, 2), date(2017, 5, 4), date(2017, 5, 9)]);
+        assert_eq!(days("FREQ=DAILY;UNTIL=20170503", date(2017, 5, 1), date(2017, 5, 31)),
+                   vec![date(2017, 5, 1), date(2017, 5, 2), date(2017, 5, 3)]);
+    }
+
+    #[test]
+    fn exceptions_and_text() {
+        let text = "rrule: FREQ=WEEKLY;BYDAY=WE\nstart: 2017-05-31\nhour: 15\nexcept: 2017-06-07\n\nMeeting\n";
+        let recurring = Recurring::parse("id", text).unwrap();
+        assert_eq!(recurring.hour, Some(15));
+        assert_eq!(recurring.text, "Meeting");
+        assert!(recurring.occurs_on(date(2017, 5, 31)));
+        assert!(!recurring.occurs_on(date(2017, 6, 7)));
+        assert!(recurring.occurs_on(date(2017, 6, 14)));
+        assert_eq!(recurring.to_text(), text);
+
+        assert!(Recurring::parse("id", "start: 2017-05-31\n").is_err());
+        assert!(Recurring::parse("id", "rrule: FREQ=DAILY\nstart: 2017-05-31\nhour: 24\n").is_err());
+    }
+
+    #[test]
+    fn nth_weekday() {
+        assert_eq!(nth_weekday_of_month(2017, 5, 0, 1), Some(date(2017, 5, 1)));
+        assert_eq!(nth_weekday_of_month(2017, 5, 2, 5), Some(date(2017, 5, 31)));
+        assert_eq!(nth_weekday_of_month(2017, 5, 3, 5), None);
+        assert_eq!(nth_weekday_of_month(2017, 5, 2, -1), Some(date(2017, 5, 31)));
+        assert_eq!(nth_weekday_of_month(2017, 5, 0, -5), Some(date(2017, 5, 1)));
+        assert_eq!(nth_weekday_of_month(2017, 5, 0, i32::MAX), None);
+        assert_eq!(nth_weekday_of_month(2017, 5, 0, i32::MIN), None);
+        assert_eq!(nth_weekday_of_month(262143, 12, 0, 2000000000), None);
+        assert_eq!(nth_weekday_of_month(-262144, 1, 0, -2000000000), None);
+    }
+
+    #[test]
+    fn byday_ordinals() {
+        assert!(RRule::parse("FREQ=YEARLY;BYDAY=53MO").is_ok());
+        assert!(RRule::parse("FREQ=YEARLY;BYDAY=-53MO").is_ok());
+        assert!(RRule::parse("FREQ=YEARLY;BYDAY=54MO").is_err());
+        assert!(RRule::parse("FREQ=MONTHLY;BYDAY=0MO").is_err());
+        assert!(RRule::parse("FREQ=MONTHLY;BYDAY=2000000000MO").is_err());
+        assert!(RRule::parse("FREQ=MONTHLY;BYDAY=-2147483648MO").is_err());
+        assert_eq!(days("FREQ=MONTHLY;BYDAY=53MO", date(2017, 5, 1), date(2017, 7, 31)), vec![]);
+    }
+}
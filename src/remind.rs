@@ -0,0 +1,323 @@
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::offset::local::Local;
+use chrono::Duration;
+
+use xdg;
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+
+use ::config::{RemindConfig, Sink};
+use ::database::Database;
+use ::editor::split_command;
+use ::one_day;
+
+/// How often the database is checked for upcoming entries.
+const POLL_SECONDS: i64 = 30;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+struct Reminder {
+    /// Identifies the reminder in the state file so that it's only sent once.
+    key: String,
+    start: NaiveDateTime,
+    title: String,
+    body: String,
+}
+
+/// Sends reminders for hour entries and events as they come up. Reminders that have been sent
+/// are recorded in `$XDG_STATE_HOME/callus/reminders` so they aren't sent again after a restart.
+/// If `once` is set the database is only checked once rather than forever.
+pub fn run(db: &Database, config: &RemindConfig, once: bool) -> Result<(), io::Error> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("callus")?;
+    let state_path = xdg_dirs.place_state_file("reminders")?;
+    let mut fired = load_fired(&state_path);
+    let lead = Duration::minutes(config.lead_minutes as i64);
+
+    loop {
+        db.reload();
+        let now = Local::now().naive_local();
+        for reminder in upcoming(db, now, lead) {
+            if fired.contains(&reminder.key) {
+                continue;
+            }
+            if let Err(e) = send(config, &reminder) {
+                match once {
+                    true => return Err(e),
+                    false => {
+                        eprintln!("callus remind: {}", e);
+                        continue;
+                    },
+                }
+            }
+            fired.insert(reminder.key);
+            save_fired(&state_path, &fired)?;
+        }
+
+        // Forget reminders for things that are long over, so the state file doesn't grow forever.
+        let before = fired.len();
+        fired.retain(|key| {
+            match NaiveDateTime::parse_from_str(key.split(' ').next().unwrap_or(""), TIME_FORMAT) {
+                Ok(start) => start > now - one_day(),
+                Err(..) => false,
+            }
+        });
+        if fired.len() != before {
+            save_fired(&state_path, &fired)?;
+        }
+
+        if once {
+            return Ok(());
+        }
+        thread::sleep(Duration::seconds(POLL_SECONDS).to_std().unwrap());
+    }
+}
+
+/// Calls `notify` with a message listing the reminders that have come due, from another thread, until it
+/// returns `false`. This is how the calendar shows reminders, so nothing is recorded in the state
+/// file and `callus remind` still sends them.
+pub fn spawn<F: Fn(String) -> bool + Send + 'static>(db: Arc<Database>, config: &RemindConfig, notify: F) {
+    let lead = Duration::minutes(config.lead_minutes as i64);
+    thread::spawn(move || {
+        let mut fired = HashSet::new();
+        loop {
+            let now = Local::now().naive_local();
+            let due = upcoming(&db, now, lead)
+                .into_iter()
+                .filter(|reminder| fired.insert(reminder.key.clone()))
+                .map(|reminder| format!("{} {}", reminder.start.format("%H:%M"), reminder.title))
+                .collect::<Vec<_>>();
+            if !due.is_empty() && !notify(format!("reminder: {}", due.join(", "))) {
+                return;
+            }
+            fired.retain(|key: &String| {
+                match NaiveDateTime::parse_from_str(key.split(' ').next().unwrap_or(""), TIME_FORMAT) {
+                    Ok(start) => start > now - one_day(),
+                    Err(..) => false,
+                }
+            });
+            thread::sleep(Duration::seconds(POLL_SECONDS).to_std().unwrap());
+        }
+    });
+}
+
+/// The reminders that are due at `now`: those for entries starting within `lead` of now, or that
+/// started less than a poll interval ago.
+fn upcoming(db: &Database, now: NaiveDateTime, lead: Duration) -> Vec<Reminder> {
+    let is_due = |start: NaiveDateTime| start - lead <= now && now < start + Duration::seconds(POLL_SECONDS);
+    let mut reminders = Vec::new();
+    let mut day = (now - Duration::seconds(POLL_SECONDS)).date();
+    while day <= (now + lead).date() {
+        for hour in 0..24 {
+            let start = day.and_hms(hour as u32, 0, 0);
+            if !is_due(start) {
+                continue;
+            }
+            let text = db.get_hour(day, hour);
+            let title = match text.lines().find(|line| !line.trim().is_empty()) {
+                Some(line) => line.trim(),
+                None => continue,
+            };
+            reminders.push(Reminder {
+                key: format!("{} hour", start.format(TIME_FORMAT)),
+                start,
+                title: String::from(title),
+                body: String::from(text.trim_end()),
+            });
+        }
+        for event in db.get_events(day) {
+            if event.all_day || event.start.date() != day || !is_due(event.start) {
+                continue;
+            }
+            let mut body = event.time_range();
+            if let Some(ref location) = event.location {
+                body.push_str(&format!(" @ {}", location));
+            }
+            if let Some(ref description) = event.description {
+                body.push('\n');
+                body.push_str(description.trim_end());
+            }
+            reminders.push(Reminder {
+                key: format!("{} event:{}", event.start.format(TIME_FORMAT), event.uid),
+                start: event.start,
+                title: event.title.clone(),
+                body,
+            });
+        }
+        day = day + one_day();
+    }
+    reminders.sort_by_key(|reminder| reminder.start);
+    reminders
+}
+
+fn send(config: &RemindConfig, reminder: &Reminder) -> Result<(), io::Error> {
+    let time = reminder.start.format("%H:%M").to_string();
+    match config.sink {
+        Sink::Stdout => {
+            println!("{} {}", time, reminder.title);
+            for line in reminder.body.lines().skip_while(|line| line.trim() == reminder.title) {
+                println!("    {}", line);
+            }
+            io::stdout().flush()
+        },
+        Sink::Command => {
+            let command = match config.command {
+                Some(ref command) => split_command(command),
+                None => Vec::new(),
+            };
+            if command.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "remind sink is `command` but no command is configured"));
+            }
+            let placeholders = ["{time}", "{title}", "{body}"];
+            let mut args = command[1..].iter().map(|arg| {
+                arg.replace("{time}", &time).replace("{title}", &reminder.title).replace("{body}", &reminder.body)
+            }).collect::<Vec<_>>();
+            if !command.iter().any(|arg| placeholders.iter().any(|p| arg.contains(p))) {
+                args.push(format!("{} {}", time, reminder.title));
+                args.push(reminder.body.clone());
+            }
+            run_command(&command[0], Command::new(&command[0]).args(&args))
+        },
+        Sink::Dbus => {
+            let args = [
+                "call", "--session",
+                "--dest", "org.freedesktop.Notifications",
+                "--object-path", "/org/freedesktop/Notifications",
+                "--method", "org.freedesktop.Notifications.Notify",
+                "--",
+                "\"callus\"", "uint32 0", "\"\"",
+                &gvariant_string(&format!("{} {}", time, reminder.title)),
+                &gvariant_string(&reminder.body),
+                "@as []", "@a{sv} {}", "int32 -1",
+            ];
+            // gdbus prints the notification's id, which isn't interesting.
+            run_command("gdbus", Command::new("gdbus").args(args).stdout(Stdio::null()))
+        },
+    }
+}
+
+fn run_command(program: &str, command: &mut Command) -> Result<(), io::Error> {
+    let status = command.status().map_err(|e| {
+        io::Error::new(e.kind(), format!("failed to run `{}`: {}", program, e))
+    })?;
+    if !status.success() {
+        return Err(io::Error::other(format!("`{}` exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+/// Quotes a string in the GVariant text format that `gdbus` parses its arguments with.
+fn gvariant_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            },
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn load_fired(path: &Path) -> HashSet<String> {
+    let mut s = String::new();
+    match File::open(path) {
+        Ok(mut f) => match f.read_to_string(&mut s) {
+            Ok(..) => s.lines().map(String::from).collect(),
+            Err(..) => HashSet::new(),
+        },
+        Err(..) => HashSet::new(),
+    }
+}
+
+/// Writes the state file by renaming a new file over it, so it's never left half written.
+fn save_fired(path: &Path, fired: &HashSet<String>) -> Result<(), io::Error> {
+    let mut keys = fired.iter().collect::<Vec<_>>();
+    keys.sort();
+    let mut tmp = PathBuf::from(path);
+    tmp.set_extension("tmp");
+    {
+        let mut f = File::create(&tmp)?;
+        for key in keys {
+            writeln!(f, "{}", key)?;
+        }
+    }
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::date::NaiveDate;
+
+    use super::*;
+    use ::database::Slot;
+    use ::editor::Editor;
+    use ::event::Event;
+    use ::storage::memory::MemoryStorage;
+    use ::TempDir;
+
+    #[test]
+    fn upcoming_entries() {
+        let db = Database::new(Box::new(MemoryStorage::new()), Editor::new(None));
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Hour(day, 15), "\nMeeting\nbring notes\n").unwrap();
+        db.set(Slot::Hour(day, 16), "Later").unwrap();
+        let mut event = Event::new(day, 14);
+        event.uid = String::from("dentist");
+        event.title = String::from("Dentist");
+        event.start = day.and_hms(14, 50, 0);
+        event.location = Some(String::from("High Street"));
+        db.put_event(&event).unwrap();
+
+        let keys = |now: NaiveDateTime| {
+            upcoming(&db, now, Duration::minutes(15)).into_iter().map(|reminder| reminder.key).collect::<Vec<_>>()
+        };
+        assert_eq!(keys(day.and_hms(14, 30, 0)), Vec::<String>::new());
+        assert_eq!(keys(day.and_hms(14, 35, 0)), vec!["2017-05-31T14:50 event:dentist"]);
+        assert_eq!(keys(day.and_hms(14, 45, 0)), vec!["2017-05-31T14:50 event:dentist", "2017-05-31T15:00 hour"]);
+        assert_eq!(keys(day.and_hms(15, 0, 29)), vec!["2017-05-31T15:00 hour"]);
+        assert_eq!(keys(day.and_hms(15, 0, 30)), Vec::<String>::new());
+
+        let reminders = upcoming(&db, day.and_hms(14, 45, 0), Duration::minutes(15));
+        assert_eq!(reminders[0].body, "14:50-15:00 @ High Street");
+        assert_eq!(reminders[1].title, "Meeting");
+        assert_eq!(reminders[1].body, "\nMeeting\nbring notes");
+    }
+
+    #[test]
+    fn reminders_before_midnight() {
+        let db = Database::new(Box::new(MemoryStorage::new()), Editor::new(None));
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        db.set(Slot::Hour(day + one_day(), 0), "Fireworks").unwrap();
+        let reminders = upcoming(&db, day.and_hms(23, 50, 0), Duration::minutes(15));
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].start, (day + one_day()).and_hms(0, 0, 0));
+    }
+
+    #[test]
+    fn fired_reminders_are_kept() {
+        let dir = TempDir::new("remind-fired");
+        let path = dir.path().join("reminders");
+        assert!(load_fired(&path).is_empty());
+        let fired = ["2017-05-31T15:00 hour", "2017-05-31T14:50 event:dentist"].iter()
+            .map(|key| String::from(*key))
+            .collect::<HashSet<_>>();
+        save_fired(&path, &fired).unwrap();
+        assert_eq!(load_fired(&path), fired);
+    }
+
+    #[test]
+    fn gvariant_strings() {
+        assert_eq!(gvariant_string("15:00 \"Meeting\"\nC:\\notes"), "\"15:00 \\\"Meeting\\\"\\nC:\\\\notes\"");
+    }
+}
@@ -0,0 +1,143 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::{border, Widget, Block};
+use tui::style::Style;
+
+use termion::event::Key;
+
+use std::cell::Cell;
+use std::cmp::{min, max};
+
+use ::calendar::slot_title;
+use ::database::{SearchResult, Slot};
+use ::theme::Theme;
+
+pub enum SearchAction {
+    Continue,
+    Jump(Slot),
+    Close,
+}
+
+/// A popup listing the results of a search, one per line.
+pub struct SearchPane {
+    query: String,
+    results: Vec<SearchResult>,
+    selected: usize,
+    scroll: Cell<usize>,
+}
+
+impl SearchPane {
+    pub fn new(query: String, results: Vec<SearchResult>) -> SearchPane {
+        SearchPane {
+            query,
+            results,
+            selected: 0,
+            scroll: Cell::new(0),
+        }
+    }
+
+    pub fn key(&mut self, key: Key) -> SearchAction {
+        match key {
+            Key::Esc | Key::Char('q') => return SearchAction::Close,
+            Key::Char('\n') => return SearchAction::Jump(self.results[self.selected].slot),
+            Key::Up | Key::Char('k') if self.selected > 0 => self.selected -= 1,
+            Key::Down | Key::Char('j') if self.selected + 1 < self.results.len() => self.selected += 1,
+            Key::Home | Key::Char('g') => self.selected = 0,
+            Key::End | Key::Char('G') => self.selected = self.results.len() - 1,
+            _ => (),
+        }
+        SearchAction::Continue
+    }
+
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer, theme: &Theme) {
+        let width = min(area.width, max(40, area.width * 4 / 5));
+        let height = min(area.height, max(5, area.height * 2 / 3));
+        let rect = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        if rect.width < 3 || rect.height < 3 {
+            return;
+        }
+
+        for y in rect.top()..rect.bottom() {
+            for x in rect.left()..rect.right() {
+                buffer.get_mut(x, y).reset();
+            }
+        }
+        let title = format!("{} results for \"{}\" (Enter to jump, Esc to close)", self.results.len(), self.query);
+        let block = Block::default()
+                .title(&title)
+                .borders(border::ALL)
+                .title_style(theme.title.style());
+        block.draw(&rect, buffer);
+        let inner = block.inner(&rect);
+
+        let visible = inner.height as usize;
+        if self.selected < self.scroll.get() {
+            self.scroll.set(self.selected);
+        }
+        if self.selected >= self.scroll.get() + visible {
+            self.scroll.set(self.selected + 1 - visible);
+        }
+
+        let scroll = self.scroll.get();
+        let labels = self.results.iter().map(|result| slot_title(result.slot)).collect::<Vec<_>>();
+        let label_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+        for (i, result) in self.results.iter().enumerate().skip(scroll).take(visible) {
+            let y = inner.y + (i - scroll) as u16;
+            let style = match i == self.selected {
+                true => theme.selected.style(),
+                false => Style::default(),
+            };
+            let line = format!("{:width$}  {}", labels[i], result.line, width = label_width);
+            let line = format!("{:width$}", line, width = inner.width as usize);
+            buffer.set_stringn(inner.x, y, &line, inner.width as usize, &style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::date::NaiveDate;
+
+    use super::*;
+
+    fn pane() -> SearchPane {
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        let results = (0..3u8).map(|hour| SearchResult {
+            slot: Slot::Hour(day, hour),
+            line: format!("result {}", hour),
+        }).collect();
+        SearchPane::new(String::from("result"), results)
+    }
+
+    fn jump(pane: &mut SearchPane) -> Option<Slot> {
+        match pane.key(Key::Char('\n')) {
+            SearchAction::Jump(slot) => Some(slot),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        let mut pane = pane();
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 0)));
+        pane.key(Key::Up);
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 0)));
+        pane.key(Key::Down);
+        pane.key(Key::Char('j'));
+        pane.key(Key::Down);
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 2)));
+        pane.key(Key::Char('k'));
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 1)));
+        pane.key(Key::Char('g'));
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 0)));
+        pane.key(Key::End);
+        assert_eq!(jump(&mut pane), Some(Slot::Hour(day, 2)));
+        assert!(matches!(pane.key(Key::Esc), SearchAction::Close));
+    }
+}
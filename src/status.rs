@@ -0,0 +1,86 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::Widget;
+
+use ::prompt::Prompt;
+use ::theme::{self, Theme};
+
+/// The line at the bottom of the screen. It shows which view is open, what's selected and how
+/// many entries it has, followed by the latest message or else some hints about keys. An open
+/// prompt replaces it.
+pub struct StatusBar<'a> {
+    view: &'a str,
+    position: String,
+    entries: usize,
+    message: Option<&'a str>,
+    hints: String,
+    prompt: Option<&'a Prompt>,
+    theme: Theme,
+}
+
+impl<'a> StatusBar<'a> {
+    pub fn new(view: &'a str, position: String, entries: usize, theme: Theme) -> StatusBar<'a> {
+        StatusBar {
+            view,
+            position,
+            entries,
+            message: None,
+            hints: String::new(),
+            prompt: None,
+            theme,
+        }
+    }
+
+    pub fn message(mut self, message: Option<&'a str>) -> StatusBar<'a> {
+        self.message = message;
+        self
+    }
+
+    pub fn hints(mut self, hints: String) -> StatusBar<'a> {
+        self.hints = hints;
+        self
+    }
+
+    pub fn prompt(mut self, prompt: Option<&'a Prompt>) -> StatusBar<'a> {
+        self.prompt = prompt;
+        self
+    }
+}
+
+impl<'a> Widget for StatusBar<'a> {
+    fn draw(&self, area: &Rect, buffer: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        if let Some(prompt) = self.prompt {
+            return prompt.draw(area, buffer);
+        }
+        let width = area.width as usize;
+        let bar_style = self.theme.status.style();
+        buffer.set_stringn(area.x, area.y, &" ".repeat(width), width, &bar_style);
+
+        let entries = match self.entries {
+            0 => String::from("no entries"),
+            1 => String::from("1 entry"),
+            n => format!("{} entries", n),
+        };
+        let status = format!(" {} | {} | {} ", self.view, self.position, entries);
+        let status_len = status.chars().count().min(width);
+        buffer.set_stringn(area.x, area.y, &status, width, &theme::bold(bar_style));
+
+        let rest = width - status_len;
+        let x = area.x + status_len as u16;
+        match self.message {
+            Some(message) => {
+                let message = format!(" {} ", message);
+                buffer.set_stringn(x, area.y, &message, rest, &self.theme.message.apply(bar_style));
+            },
+            None => {
+                let hints_len = self.hints.chars().count() + 1;
+                if hints_len <= rest {
+                    buffer.set_stringn(area.right() - hints_len as u16, area.y, &self.hints, rest, &bar_style);
+                }
+            },
+        }
+    }
+}
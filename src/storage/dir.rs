@@ -0,0 +1,299 @@
+use chrono::naive::date::NaiveDate;
+use chrono::Datelike;
+use notify::{RecommendedWatcher, RecursiveMode};
+
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+use ::database::Slot;
+use ::event::Event;
+use ::recurrence::Recurring;
+use ::MONTH_NAMES;
+use super::{Change, Invalid, Storage};
+
+/// The original storage layout: a directory per day, eg. `2017/May/31`, holding `today.txt` for
+/// the day's note, `HH.txt` for each hour and an `events` directory. Recurring entries live in
+/// `recurring`. Every entry is a plain text file so the tree can be edited by hand.
+pub struct DirStorage {
+    location: PathBuf,
+    invalid: Invalid,
+}
+
+impl DirStorage {
+    pub fn new(location: PathBuf) -> DirStorage {
+        DirStorage {
+            location,
+            invalid: Invalid::default(),
+        }
+    }
+
+    fn day_dir(&self, day: NaiveDate) -> PathBuf {
+        let mut path = self.location.clone();
+        path.push(format!("{}", day.year()));
+        path.push(MONTH_NAMES[day.month0() as usize]);
+        path.push(format!("{}", day.day()));
+        path
+    }
+
+    fn filename(&self, slot: Slot) -> PathBuf {
+        match slot {
+            Slot::Day(day) => {
+                let mut path = self.day_dir(day);
+                path.push("today.txt");
+                path
+            },
+            Slot::Hour(day, hour) => {
+                let mut path = self.day_dir(day);
+                path.push(format!("{:02}.txt", hour));
+                path
+            },
+        }
+    }
+
+    fn events_dir(&self, day: NaiveDate) -> PathBuf {
+        let mut path = self.day_dir(day);
+        path.push("events");
+        path
+    }
+
+    fn event_filename(&self, event: &Event) -> PathBuf {
+        let mut path = self.events_dir(event.start.date());
+        path.push(sanitize_filename(&event.uid));
+        path
+    }
+
+    fn recurring_dir(&self) -> PathBuf {
+        let mut path = self.location.clone();
+        path.push("recurring");
+        path
+    }
+
+    fn recurring_filename(&self, recurring: &Recurring) -> PathBuf {
+        let mut path = self.recurring_dir();
+        path.push(sanitize_filename(&recurring.id));
+        path
+    }
+
+    /// Returns the date and directory of every day that has a directory in the storage tree.
+    fn day_dirs(&self) -> Vec<(NaiveDate, PathBuf)> {
+        let mut days = Vec::new();
+        for year_dir in sub_dirs(&self.location) {
+            let year = match year_dir.file_name().and_then(|name| name.to_str()).and_then(|name| name.parse().ok()) {
+                Some(year) => year,
+                None => continue,
+            };
+            for month_dir in sub_dirs(&year_dir) {
+                let month = match month_dir.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => match MONTH_NAMES.iter().position(|m| *m == name) {
+                        Some(i) => i as u32 + 1,
+                        None => continue,
+                    },
+                    None => continue,
+                };
+                for day_dir in sub_dirs(&month_dir) {
+                    let day = day_dir.file_name()
+                        .and_then(|name| name.to_str())
+                        .and_then(|name| name.parse().ok())
+                        .and_then(|day| NaiveDate::from_ymd_opt(year, month, day));
+                    if let Some(day) = day {
+                        days.push((day, day_dir));
+                    }
+                }
+            }
+        }
+        days
+    }
+}
+
+impl Storage for DirStorage {
+    fn get(&self, slot: Slot) -> Result<String, io::Error> {
+        load_entry(&self.filename(slot))
+    }
+
+    fn put(&self, slot: Slot, text: &str) -> Result<(), io::Error> {
+        save_entry(&self.filename(slot), text)
+    }
+
+    fn delete(&self, slot: Slot) -> Result<(), io::Error> {
+        save_entry(&self.filename(slot), "")
+    }
+
+    fn list_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(Slot, String)>, io::Error> {
+        let mut entries = Vec::new();
+        for (day, dir) in self.day_dirs() {
+            if day < from || day > to {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let slot = match parse_entry_filename(&name) {
+                    Some(None) => Slot::Day(day),
+                    Some(Some(hour)) => Slot::Hour(day, hour),
+                    None => continue,
+                };
+                let text = load_entry(&path)?;
+                if !text.is_empty() {
+                    entries.push((slot, text));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn events_starting(&self, day: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        load_events(&self.events_dir(day), &self.invalid)
+    }
+
+    fn all_events(&self) -> Result<Vec<Event>, io::Error> {
+        let mut events = Vec::new();
+        for (_, dir) in self.day_dirs() {
+            events.extend(load_events(&dir.join("events"), &self.invalid)?);
+        }
+        Ok(events)
+    }
+
+    fn put_event(&self, event: &Event) -> Result<(), io::Error> {
+        save_entry(&self.event_filename(event), &event.to_text())
+    }
+
+    fn delete_event(&self, event: &Event) -> Result<(), io::Error> {
+        save_entry(&self.event_filename(event), "")
+    }
+
+    fn all_recurring(&self) -> Result<Vec<Recurring>, io::Error> {
+        let mut recurring = Vec::new();
+        let entries = match fs::read_dir(self.recurring_dir()) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(recurring),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let id = path.file_name().unwrap().to_string_lossy().into_owned();
+            match Recurring::parse(&id, &load_entry(&path)?) {
+                Ok(r) => recurring.push(r),
+                Err(e) => self.invalid.add(&path.display().to_string(), &e),
+            }
+        }
+        Ok(recurring)
+    }
+
+    fn put_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        save_entry(&self.recurring_filename(recurring), &recurring.to_text())
+    }
+
+    fn delete_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        save_entry(&self.recurring_filename(recurring), "")
+    }
+
+    fn take_invalid(&self) -> Option<io::Error> {
+        self.invalid.take()
+    }
+
+    fn watch(&self, changed: Box<dyn Fn(Change) + Send>) -> Result<Option<RecommendedWatcher>, io::Error> {
+        let location = self.location.clone();
+        let to_change = move |path: &Path| change_for(&location, path);
+        super::watch_path(&self.location, RecursiveMode::Recursive, to_change, changed).map(Some)
+    }
+}
+
+pub fn sub_dirs(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+                              .map(|entry| entry.path())
+                              .filter(|path| path.is_dir())
+                              .collect(),
+        Err(..) => Vec::new(),
+    }
+}
+
+/// Works out which slot a file in a day's directory holds: `Some(None)` for the day's note,
+/// `Some(Some(hour))` for an hour entry.
+fn parse_entry_filename(name: &str) -> Option<Option<u8>> {
+    if name == "today.txt" {
+        return Some(None);
+    }
+    let hour = name.strip_suffix(".txt")?;
+    match hour.parse::<u8>() {
+        Ok(hour) if hour < 24 => Some(Some(hour)),
+        _ => None,
+    }
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '@' => c,
+        _ => '_',
+    }).collect()
+}
+
+fn load_entry(path: &Path) -> Result<String, io::Error> {
+    let mut s = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+        Ok(..) => Ok(s),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(with_path(path, e)),
+    }
+}
+
+/// Loads the events in a day's `events` directory. Files that aren't valid events are skipped,
+/// and the first of them kept in `invalid`.
+fn load_events(dir: &Path, invalid: &Invalid) -> Result<Vec<Event>, io::Error> {
+    let mut events = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(events),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let uid = path.file_name().unwrap().to_string_lossy().into_owned();
+        match Event::parse(&uid, &load_entry(&path)?) {
+            Ok(event) => events.push(event),
+            Err(e) => invalid.add(&path.display().to_string(), &e),
+        }
+    }
+    Ok(events)
+}
+
+/// What a change to `path` means for the storage tree at `location`, or `None` if `path` isn't
+/// part of it.
+fn change_for(location: &Path, path: &Path) -> Option<Change> {
+    let names = path.strip_prefix(location).ok()?
+        .iter()
+        .map(|name| name.to_str())
+        .collect::<Option<Vec<_>>>()?;
+    match names[..] {
+        [] => Some(Change::All),
+        ["recurring", ..] => Some(Change::Recurring),
+        [year, month, day, ..] => {
+            let month = MONTH_NAMES.iter().position(|m| *m == month)? as u32 + 1;
+            NaiveDate::from_ymd_opt(year.parse().ok()?, month, day.parse().ok()?).map(Change::Day)
+        },
+        // A whole year or month was added or removed.
+        [year, ..] if year.parse::<i32>().is_ok() => Some(Change::All),
+        _ => None,
+    }
+}
+
+/// Writes an entry, removing its file instead if the entry is now empty.
+fn save_entry(path: &Path, text: &str) -> Result<(), io::Error> {
+    let res = match text.is_empty() {
+        true => match fs::remove_file(path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        },
+        false => path.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| File::create(path))
+            .and_then(|mut f| f.write_all(text.as_bytes())),
+    };
+    res.map_err(|e| with_path(path, e))
+}
+
+/// Adds the path to an error's message, since the errors from `std::fs` don't say which file
+/// they're about.
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,741 @@
+use chrono::naive::date::NaiveDate;
+use notify::RecommendedWatcher;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ::database::Slot;
+use ::event::Event;
+use ::recurrence::Recurring;
+use super::{Change, Invalid, Storage};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const RECURRING_HEADING: &str = "Recurring";
+const EVENT_PREFIX: &str = "event ";
+
+/// Keeps everything in one human-editable text file:
+///
+/// ```text
+/// ## 2017-05-31
+/// The day's note.
+///
+/// ### 15:00
+/// What's happening at 3PM.
+///
+/// ### event 1496239200-1234@callus
+/// title: Meeting
+/// ...
+///
+/// ## Recurring
+///
+/// ### 1496239200-5678@callus
+/// rrule: FREQ=WEEKLY;BYDAY=WE
+/// ...
+/// ```
+///
+/// Anything callus doesn't recognise, such as text before the first heading or a `## Ideas`
+/// section, is written back as it was. Lines of an entry starting with `##` or `\` are escaped
+/// with a `\` so that they aren't read as headings.
+pub struct JournalStorage {
+    path: PathBuf,
+    cached: Mutex<Cached>,
+    invalid: Invalid,
+}
+
+/// The parsed file and its modification time when it was read, so that edits made outside callus
+/// are noticed.
+struct Cached {
+    journal: Journal,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Default)]
+struct Journal {
+    /// Lines before the first `##` heading.
+    preamble: Vec<String>,
+    sections: Vec<Section>,
+}
+
+/// A `##` heading and everything up to the next one.
+struct Section {
+    heading: String,
+    kind: SectionKind,
+    /// Lines between the heading and the first `###` heading. For a day this is its note.
+    body: Vec<String>,
+    subsections: Vec<Subsection>,
+}
+
+#[derive(PartialEq)]
+enum SectionKind {
+    Day(NaiveDate),
+    Recurring,
+    Other,
+}
+
+/// A `###` heading and everything up to the next heading.
+struct Subsection {
+    heading: String,
+    kind: SubsectionKind,
+    body: Vec<String>,
+}
+
+#[derive(PartialEq)]
+enum SubsectionKind {
+    Hour(u8),
+    Event(String),
+    Recurring(String),
+    Other,
+}
+
+impl JournalStorage {
+    pub fn open(path: PathBuf) -> Result<JournalStorage, io::Error> {
+        let storage = JournalStorage {
+            path,
+            cached: Mutex::new(Cached {
+                journal: Journal::default(),
+                modified: None,
+            }),
+            invalid: Invalid::default(),
+        };
+        drop(storage.load()?);
+        Ok(storage)
+    }
+
+    /// Returns the journal, reading the file again if it has changed since it was last read.
+    fn load(&self) -> Result<MutexGuard<'_, Cached>, io::Error> {
+        let mut cached = self.cached.lock().unwrap();
+        let modified = match fs::metadata(&self.path) {
+            Ok(metadata) => Some(metadata.modified()?),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if modified != cached.modified {
+            let mut s = String::new();
+            if modified.is_some() {
+                File::open(&self.path)?.read_to_string(&mut s)?;
+            }
+            cached.journal = Journal::parse(&s);
+            cached.modified = modified;
+        }
+        Ok(cached)
+    }
+
+    /// The events on the days `include` picks out. Events that aren't valid are skipped, and the
+    /// first of them kept for `take_invalid`.
+    fn events<F: Fn(NaiveDate) -> bool>(&self, journal: &Journal, include: F) -> Vec<Event> {
+        let mut events = Vec::new();
+        for section in &journal.sections {
+            match section.kind {
+                SectionKind::Day(day) if include(day) => (),
+                _ => continue,
+            }
+            for subsection in &section.subsections {
+                if let SubsectionKind::Event(ref uid) = subsection.kind {
+                    match Event::parse(uid, &body_text(&subsection.body)) {
+                        Ok(event) => events.push(event),
+                        Err(e) => self.invalid.add(&format!("{}: event `{}`", self.path.display(), uid), &e),
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    /// Changes the journal and writes it back to the file.
+    fn update<F: FnOnce(&mut Journal)>(&self, f: F) -> Result<(), io::Error> {
+        let mut cached = self.load()?;
+        f(&mut cached.journal);
+        write_atomically(&self.path, &cached.journal.to_text())?;
+        cached.modified = Some(fs::metadata(&self.path)?.modified()?);
+        Ok(())
+    }
+}
+
+impl Storage for JournalStorage {
+    fn get(&self, slot: Slot) -> Result<String, io::Error> {
+        let cached = self.load()?;
+        let journal = &cached.journal;
+        Ok(match slot {
+            Slot::Day(day) => journal.day(day).map(|section| body_text(&section.body)),
+            Slot::Hour(day, hour) => journal.day(day)
+                .and_then(|section| section.subsection(&SubsectionKind::Hour(hour)))
+                .map(|subsection| body_text(&subsection.body)),
+        }.unwrap_or_default())
+    }
+
+    fn put(&self, slot: Slot, text: &str) -> Result<(), io::Error> {
+        self.update(|journal| {
+            match slot {
+                Slot::Day(day) => journal.day_mut(day).body = body_lines(text),
+                Slot::Hour(day, hour) => {
+                    journal.day_mut(day).subsection_mut(SubsectionKind::Hour(hour)).body = body_lines(text);
+                },
+            }
+        })
+    }
+
+    fn delete(&self, slot: Slot) -> Result<(), io::Error> {
+        self.update(|journal| {
+            let day = match slot {
+                Slot::Day(day) | Slot::Hour(day, _) => day,
+            };
+            if let Some(i) = journal.sections.iter().position(|section| section.kind == SectionKind::Day(day)) {
+                match slot {
+                    Slot::Day(..) => journal.sections[i].body = vec![String::new()],
+                    Slot::Hour(_, hour) => journal.sections[i].remove(&SubsectionKind::Hour(hour)),
+                }
+                journal.remove_if_empty(i);
+            }
+        })
+    }
+
+    fn list_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(Slot, String)>, io::Error> {
+        let cached = self.load()?;
+        let mut entries = Vec::new();
+        for section in &cached.journal.sections {
+            let day = match section.kind {
+                SectionKind::Day(day) if from <= day && day <= to => day,
+                _ => continue,
+            };
+            let text = body_text(&section.body);
+            if !text.is_empty() {
+                entries.push((Slot::Day(day), text));
+            }
+            for subsection in &section.subsections {
+                if let SubsectionKind::Hour(hour) = subsection.kind {
+                    let text = body_text(&subsection.body);
+                    if !text.is_empty() {
+                        entries.push((Slot::Hour(day, hour), text));
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn events_starting(&self, day: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        self.events_starting_range(day, day)
+    }
+
+    fn events_starting_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        let cached = self.load()?;
+        Ok(self.events(&cached.journal, |day| from <= day && day <= to))
+    }
+
+    fn all_events(&self) -> Result<Vec<Event>, io::Error> {
+        let cached = self.load()?;
+        Ok(self.events(&cached.journal, |_| true))
+    }
+
+    fn put_event(&self, event: &Event) -> Result<(), io::Error> {
+        self.update(|journal| {
+            let kind = SubsectionKind::Event(event.uid.clone());
+            journal.day_mut(event.start.date()).subsection_mut(kind).body = body_lines(&event.to_text());
+        })
+    }
+
+    fn delete_event(&self, event: &Event) -> Result<(), io::Error> {
+        self.update(|journal| {
+            let day = SectionKind::Day(event.start.date());
+            if let Some(i) = journal.sections.iter().position(|section| section.kind == day) {
+                journal.sections[i].remove(&SubsectionKind::Event(event.uid.clone()));
+                journal.remove_if_empty(i);
+            }
+        })
+    }
+
+    fn all_recurring(&self) -> Result<Vec<Recurring>, io::Error> {
+        let cached = self.load()?;
+        let mut recurring = Vec::new();
+        for section in cached.journal.sections.iter().filter(|section| section.kind == SectionKind::Recurring) {
+            for subsection in &section.subsections {
+                if let SubsectionKind::Recurring(ref id) = subsection.kind {
+                    match Recurring::parse(id, &body_text(&subsection.body)) {
+                        Ok(r) => recurring.push(r),
+                        Err(e) => self.invalid.add(&format!("{}: recurring entry `{}`", self.path.display(), id), &e),
+                    }
+                }
+            }
+        }
+        Ok(recurring)
+    }
+
+    fn put_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        self.update(|journal| {
+            let kind = SubsectionKind::Recurring(recurring.id.clone());
+            journal.recurring_mut().subsection_mut(kind).body = body_lines(&recurring.to_text());
+        })
+    }
+
+    fn delete_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        self.update(|journal| {
+            if let Some(i) = journal.sections.iter().position(|section| section.kind == SectionKind::Recurring) {
+                journal.sections[i].remove(&SubsectionKind::Recurring(recurring.id.clone()));
+                journal.remove_if_empty(i);
+            }
+        })
+    }
+
+    fn take_invalid(&self) -> Option<io::Error> {
+        self.invalid.take()
+    }
+
+    fn watch(&self, changed: Box<dyn Fn(Change) + Send>) -> Result<Option<RecommendedWatcher>, io::Error> {
+        super::watch_file(&self.path, changed).map(Some)
+    }
+}
+
+impl Journal {
+    fn parse(text: &str) -> Journal {
+        let mut journal = Journal::default();
+        for line in text.lines() {
+            if let Some(title) = line.strip_prefix("### ") {
+                if let Some(section) = journal.sections.last_mut() {
+                    let kind = SubsectionKind::parse(&section.kind, title.trim());
+                    section.subsections.push(Subsection {
+                        heading: String::from(line),
+                        kind,
+                        body: Vec::new(),
+                    });
+                    continue;
+                }
+            } else if let Some(title) = line.strip_prefix("## ") {
+                journal.sections.push(Section {
+                    heading: String::from(line),
+                    kind: SectionKind::parse(title.trim()),
+                    body: Vec::new(),
+                    subsections: Vec::new(),
+                });
+                continue;
+            }
+            let body = match journal.sections.last_mut() {
+                Some(section) => match section.subsections.last_mut() {
+                    Some(subsection) => &mut subsection.body,
+                    None => &mut section.body,
+                },
+                None => &mut journal.preamble,
+            };
+            body.push(String::from(line));
+        }
+        journal
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut push = |line: &str| {
+            text.push_str(line);
+            text.push('\n');
+        };
+        self.preamble.iter().for_each(|line| push(line));
+        for section in &self.sections {
+            push(&section.heading);
+            section.body.iter().for_each(|line| push(line));
+            for subsection in &section.subsections {
+                push(&subsection.heading);
+                subsection.body.iter().for_each(|line| push(line));
+            }
+        }
+        text
+    }
+
+    fn day(&self, day: NaiveDate) -> Option<&Section> {
+        self.sections.iter().find(|section| section.kind == SectionKind::Day(day))
+    }
+
+    /// Returns the day's section, adding it after the days before it if it doesn't exist.
+    fn day_mut(&mut self, day: NaiveDate) -> &mut Section {
+        let kind = SectionKind::Day(day);
+        if let Some(i) = self.sections.iter().position(|section| section.kind == kind) {
+            return &mut self.sections[i];
+        }
+        let is_day = |section: &Section| matches!(section.kind, SectionKind::Day(..));
+        let later = self.sections.iter().position(|section| match section.kind {
+            SectionKind::Day(d) => d > day,
+            _ => false,
+        });
+        let i = later
+            .or_else(|| self.sections.iter().rposition(is_day).map(|i| i + 1))
+            .or_else(|| self.sections.iter().position(|section| section.kind == SectionKind::Recurring))
+            .unwrap_or(self.sections.len());
+        self.separate(i);
+        self.sections.insert(i, Section::new(format!("## {}", day.format(DATE_FORMAT)), kind));
+        &mut self.sections[i]
+    }
+
+    /// Returns the recurring section, adding it at the end if it doesn't exist.
+    fn recurring_mut(&mut self) -> &mut Section {
+        let i = match self.sections.iter().position(|section| section.kind == SectionKind::Recurring) {
+            Some(i) => i,
+            None => {
+                let heading = format!("## {}", RECURRING_HEADING);
+                self.separate(self.sections.len());
+                self.sections.push(Section::new(heading, SectionKind::Recurring));
+                self.sections.len() - 1
+            },
+        };
+        &mut self.sections[i]
+    }
+
+    /// Makes sure there's a blank line before a section about to be inserted at `i`.
+    fn separate(&mut self, i: usize) {
+        match i {
+            0 => ensure_blank_line(&mut self.preamble),
+            i => ensure_blank_line(self.sections[i - 1].last_lines_mut()),
+        }
+    }
+
+    /// Removes the section at `i` if nothing is left in it.
+    fn remove_if_empty(&mut self, i: usize) {
+        let section = &self.sections[i];
+        if section.subsections.is_empty() && body_text(&section.body).is_empty() {
+            self.sections.remove(i);
+        }
+    }
+}
+
+impl Section {
+    fn new(heading: String, kind: SectionKind) -> Section {
+        Section {
+            heading,
+            kind,
+            body: vec![String::new()],
+            subsections: Vec::new(),
+        }
+    }
+
+    fn subsection(&self, kind: &SubsectionKind) -> Option<&Subsection> {
+        self.subsections.iter().find(|subsection| subsection.kind == *kind)
+    }
+
+    /// Returns the subsection, adding it if it doesn't exist. Hours are kept in order before
+    /// everything else.
+    fn subsection_mut(&mut self, kind: SubsectionKind) -> &mut Subsection {
+        if let Some(i) = self.subsections.iter().position(|subsection| subsection.kind == kind) {
+            return &mut self.subsections[i];
+        }
+        let i = match kind {
+            SubsectionKind::Hour(hour) => {
+                let is_hour = |subsection: &Subsection| matches!(subsection.kind, SubsectionKind::Hour(..));
+                self.subsections.iter()
+                    .position(|subsection| match subsection.kind {
+                        SubsectionKind::Hour(h) => h > hour,
+                        _ => false,
+                    })
+                    .or_else(|| self.subsections.iter().rposition(is_hour).map(|i| i + 1))
+                    .unwrap_or(0)
+            },
+            _ => self.subsections.len(),
+        };
+        match i {
+            0 => ensure_blank_line(&mut self.body),
+            i => ensure_blank_line(&mut self.subsections[i - 1].body),
+        }
+        let heading = match kind {
+            SubsectionKind::Hour(hour) => format!("### {:02}:00", hour),
+            SubsectionKind::Event(ref uid) => format!("### {}{}", EVENT_PREFIX, uid),
+            SubsectionKind::Recurring(ref id) => format!("### {}", id),
+            SubsectionKind::Other => String::from("###"),
+        };
+        self.subsections.insert(i, Subsection {
+            heading,
+            kind,
+            body: Vec::new(),
+        });
+        &mut self.subsections[i]
+    }
+
+    /// The lines at the end of the section, just before the next heading.
+    fn last_lines_mut(&mut self) -> &mut Vec<String> {
+        match self.subsections.last_mut() {
+            Some(subsection) => &mut subsection.body,
+            None => &mut self.body,
+        }
+    }
+
+    fn remove(&mut self, kind: &SubsectionKind) {
+        self.subsections.retain(|subsection| subsection.kind != *kind);
+    }
+}
+
+impl SectionKind {
+    /// Works out what a `##` heading is. A day's heading may have more text after the date.
+    fn parse(title: &str) -> SectionKind {
+        if title.eq_ignore_ascii_case(RECURRING_HEADING) {
+            return SectionKind::Recurring;
+        }
+        let date = title.split_whitespace().next().unwrap_or("");
+        match NaiveDate::parse_from_str(date, DATE_FORMAT) {
+            Ok(day) => SectionKind::Day(day),
+            Err(..) => SectionKind::Other,
+        }
+    }
+}
+
+impl SubsectionKind {
+    fn parse(section: &SectionKind, title: &str) -> SubsectionKind {
+        match *section {
+            SectionKind::Day(..) => {
+                if let Some(uid) = title.strip_prefix(EVENT_PREFIX) {
+                    return SubsectionKind::Event(String::from(uid.trim()));
+                }
+                match parse_hour(title) {
+                    Some(hour) => SubsectionKind::Hour(hour),
+                    None => SubsectionKind::Other,
+                }
+            },
+            SectionKind::Recurring if !title.is_empty() => SubsectionKind::Recurring(String::from(title)),
+            _ => SubsectionKind::Other,
+        }
+    }
+}
+
+/// Parses an hour heading such as `15:00`. Headings for times that aren't on the hour aren't
+/// entries callus knows about.
+fn parse_hour(title: &str) -> Option<u8> {
+    let (hour, minute) = title.split_once(':')?;
+    match (hour.parse::<u8>().ok()?, minute) {
+        (hour, "00") if hour < 24 => Some(hour),
+        _ => None,
+    }
+}
+
+/// The text of an entry stored as `lines`, without the blank lines around it.
+fn body_text(lines: &[String]) -> String {
+    let start = match lines.iter().position(|line| !line.trim().is_empty()) {
+        Some(start) => start,
+        None => return String::new(),
+    };
+    let end = lines.iter().rposition(|line| !line.trim().is_empty()).unwrap() + 1;
+    let mut text = String::new();
+    for line in &lines[start..end] {
+        text.push_str(line.strip_prefix('\\').unwrap_or(line));
+        text.push('\n');
+    }
+    text
+}
+
+/// Adds a blank line to the end of `lines` unless it's empty or already ends with one.
+fn ensure_blank_line(lines: &mut Vec<String>) {
+    if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+        lines.push(String::new());
+    }
+}
+
+/// The lines to store an entry's text as, followed by a blank line to separate it from the next
+/// heading.
+fn body_lines(text: &str) -> Vec<String> {
+    let mut lines = text.lines()
+        .map(|line| match line.starts_with("##") || line.starts_with('\\') {
+            true => format!("\\{}", line),
+            false => String::from(line),
+        })
+        .collect::<Vec<_>>();
+    lines.push(String::new());
+    lines
+}
+
+/// Writes the file by writing a temporary file next to it and renaming it into place, so that the
+/// journal is never left half-written.
+fn write_atomically(path: &Path, text: &str) -> Result<(), io::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let (tmp, mut f) = create_temp_file(path)?;
+    let res = f.write_all(text.as_bytes()).and_then(|()| f.sync_all());
+    drop(f);
+    match res.and_then(|()| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        },
+    }
+}
+
+/// Creates a new file next to `path` to write it out to. The name is new to this process and
+/// write, so that two callus instances saving at once don't write into the same file, and ends in
+/// `.tmp` so that watching the journal ignores it.
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File), io::Error> {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    for attempt in 0..100 {
+        let tmp = path.with_file_name(format!("{}.{}-{}-{}.tmp", name, process::id(), nanos, attempt));
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(f) => return Ok((tmp, f)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "couldn't make a temporary file to save the journal"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::date::NaiveDate;
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::process;
+
+    use ::database::{Database, Slot};
+    use ::editor::Editor;
+    use ::event::Event;
+    use ::recurrence::Recurring;
+    use ::storage::Storage;
+    use super::{Journal, JournalStorage};
+
+    const TEXT: &str = "\
+# My journal
+
+## Ideas
+Things callus doesn't know about.
+
+## 2017-05-31 Wednesday
+The day's note.
+
+### 09:30
+Not an hour entry.
+
+### 15:00
+\\## Not a heading
+\\\\ starts with a backslash
+
+## Recurring
+
+### 1496239200-5678@callus
+rrule: FREQ=WEEKLY;BYDAY=WE
+start: 2017-05-31
+hour: 15
+except: 
+
+Weekly meeting
+";
+
+    /// A path for a journal that doesn't exist yet, removed again when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> TempPath {
+            TempPath(env::temp_dir().join(format!("callus-test-{}-{}.md", process::id(), name)))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn read(path: &TempPath) -> String {
+        let mut s = String::new();
+        File::open(&path.0).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn parse_keeps_text() {
+        assert_eq!(Journal::parse(TEXT).to_text(), TEXT);
+    }
+
+    #[test]
+    fn reads_entries() {
+        let path = TempPath::new("reads");
+        fs::write(&path.0, TEXT).unwrap();
+        let storage = JournalStorage::open(path.0.clone()).unwrap();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        assert_eq!(storage.get(Slot::Day(day)).unwrap(), "The day's note.\n");
+        assert_eq!(storage.get(Slot::Hour(day, 9)).unwrap(), "");
+        assert_eq!(storage.get(Slot::Hour(day, 15)).unwrap(), "## Not a heading\n\\ starts with a backslash\n");
+        let recurring = storage.all_recurring().unwrap();
+        assert_eq!(recurring.len(), 1);
+        assert_eq!(recurring[0].text, "Weekly meeting");
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = TempPath::new("round-trip");
+        fs::write(&path.0, "# My journal\n\n## Ideas\nSomething.\n").unwrap();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        let mut event = Event::new(day, 10);
+        event.title = String::from("Dentist");
+        event.location = Some(String::from("High Street"));
+        let mut recurring = Recurring::new(String::from("weekly"), day, Some(15));
+        recurring.text = String::from("Meeting");
+        {
+            let storage = JournalStorage::open(path.0.clone()).unwrap();
+            storage.put(Slot::Day(day + ::one_day()), "Later\n").unwrap();
+            storage.put(Slot::Day(day), "Note\n## not a heading\n").unwrap();
+            storage.put(Slot::Hour(day, 15), "Meeting\n").unwrap();
+            storage.put_event(&event).unwrap();
+            storage.put_recurring(&recurring).unwrap();
+        }
+        assert!(read(&path).starts_with("# My journal\n\n## Ideas\nSomething.\n"));
+        assert!(read(&path).contains("\n\\## not a heading\n"));
+
+        let storage = JournalStorage::open(path.0.clone()).unwrap();
+        assert_eq!(storage.get(Slot::Day(day)).unwrap(), "Note\n## not a heading\n");
+        assert_eq!(storage.get(Slot::Hour(day, 15)).unwrap(), "Meeting\n");
+        assert_eq!(storage.list_range(day, day).unwrap().len(), 2);
+        assert_eq!(storage.events_starting(day).unwrap(), vec![event.clone()]);
+        assert_eq!(storage.all_recurring().unwrap(), vec![recurring.clone()]);
+
+        storage.delete(Slot::Day(day)).unwrap();
+        storage.delete(Slot::Hour(day, 15)).unwrap();
+        storage.delete_event(&event).unwrap();
+        storage.delete_recurring(&recurring).unwrap();
+        storage.delete(Slot::Day(day + ::one_day())).unwrap();
+        assert_eq!(read(&path).trim_end(), "# My journal\n\n## Ideas\nSomething.");
+    }
+
+    #[test]
+    fn reports_invalid_entries() {
+        let path = TempPath::new("invalid");
+        fs::write(&path.0, "\
+## 2017-05-31 Wednesday
+
+### event broken@callus
+No fields here
+
+## Recurring
+
+### weekly@callus
+rrule: FREQ=SOMETIMES
+").unwrap();
+        let storage = JournalStorage::open(path.0.clone()).unwrap();
+        assert_eq!(storage.all_events().unwrap(), vec![]);
+        assert!(storage.take_invalid().unwrap().to_string().contains("event `broken@callus`"));
+        assert!(storage.take_invalid().is_none());
+        assert_eq!(storage.all_recurring().unwrap(), vec![]);
+        assert!(storage.take_invalid().unwrap().to_string().contains("recurring entry `weekly@callus`"));
+
+        let db = Database::new(Box::new(storage), Editor::new(None));
+        assert!(db.get_events(NaiveDate::from_ymd(2017, 5, 31)).is_empty());
+        assert!(db.take_error().is_some());
+        assert!(db.take_error().is_none());
+    }
+
+    #[test]
+    fn temp_files_are_new() {
+        let path = TempPath::new("temp-files");
+        let (first, _) = super::create_temp_file(&path.0).unwrap();
+        let (second, _) = super::create_temp_file(&path.0).unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_string_lossy().ends_with(".tmp"));
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+
+        super::write_atomically(&path.0, "Saved\n").unwrap();
+        assert_eq!(read(&path), "Saved\n");
+        let leftovers = fs::read_dir(env::temp_dir()).unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&*path.0.file_name().unwrap().to_string_lossy()))
+            .count();
+        assert_eq!(leftovers, 1);
+    }
+}
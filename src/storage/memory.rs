@@ -0,0 +1,89 @@
+use chrono::naive::date::NaiveDate;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+use ::database::Slot;
+use ::event::Event;
+use ::recurrence::Recurring;
+use super::Storage;
+
+/// Keeps everything in memory and forgets it on exit. Useful for trying callus out and for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<HashMap<Slot, String>>,
+    /// Events by start date and uid.
+    events: Mutex<HashMap<(NaiveDate, String), Event>>,
+    recurring: Mutex<HashMap<String, Recurring>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, slot: Slot) -> Result<String, io::Error> {
+        Ok(self.entries.lock().unwrap().get(&slot).cloned().unwrap_or_default())
+    }
+
+    fn put(&self, slot: Slot, text: &str) -> Result<(), io::Error> {
+        self.entries.lock().unwrap().insert(slot, String::from(text));
+        Ok(())
+    }
+
+    fn delete(&self, slot: Slot) -> Result<(), io::Error> {
+        self.entries.lock().unwrap().remove(&slot);
+        Ok(())
+    }
+
+    fn list_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(Slot, String)>, io::Error> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.iter()
+            .filter(|&(slot, text)| !text.is_empty() && match *slot {
+                Slot::Day(day) | Slot::Hour(day, _) => from <= day && day <= to,
+            })
+            .map(|(slot, text)| (*slot, text.clone()))
+            .collect())
+    }
+
+    fn events_starting(&self, day: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        let events = self.events.lock().unwrap();
+        Ok(events.iter()
+            .filter(|&(&(start, _), _)| start == day)
+            .map(|(_, event)| event.clone())
+            .collect())
+    }
+
+    fn all_events(&self) -> Result<Vec<Event>, io::Error> {
+        Ok(self.events.lock().unwrap().values().cloned().collect())
+    }
+
+    fn put_event(&self, event: &Event) -> Result<(), io::Error> {
+        let key = (event.start.date(), event.uid.clone());
+        self.events.lock().unwrap().insert(key, event.clone());
+        Ok(())
+    }
+
+    fn delete_event(&self, event: &Event) -> Result<(), io::Error> {
+        let key = (event.start.date(), event.uid.clone());
+        self.events.lock().unwrap().remove(&key);
+        Ok(())
+    }
+
+    fn all_recurring(&self) -> Result<Vec<Recurring>, io::Error> {
+        Ok(self.recurring.lock().unwrap().values().cloned().collect())
+    }
+
+    fn put_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        self.recurring.lock().unwrap().insert(recurring.id.clone(), recurring.clone());
+        Ok(())
+    }
+
+    fn delete_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        self.recurring.lock().unwrap().remove(&recurring.id);
+        Ok(())
+    }
+}
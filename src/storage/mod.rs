@@ -0,0 +1,183 @@
+use chrono::naive::date::{self, NaiveDate};
+use notify::{self, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ::database::Slot;
+use ::event::Event;
+use ::recurrence::Recurring;
+
+pub mod dir;
+pub mod journal;
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use self::dir::DirStorage;
+pub use self::journal::JournalStorage;
+pub use self::memory::MemoryStorage;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStorage;
+
+/// Something in storage that was changed from outside callus.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// The entries or events of a day.
+    Day(NaiveDate),
+    Recurring,
+    /// Anything could have changed.
+    All,
+}
+
+/// Where `Database` keeps entries, events and recurring entries. `Database` caches what it reads,
+/// so implementations don't need to.
+pub trait Storage: Send + Sync {
+    /// The text of an entry, or an empty string if it has none.
+    fn get(&self, slot: Slot) -> Result<String, io::Error>;
+
+    fn put(&self, slot: Slot, text: &str) -> Result<(), io::Error>;
+
+    fn delete(&self, slot: Slot) -> Result<(), io::Error>;
+
+    /// Every non-empty entry on the days from `from` to `to` inclusive, in no particular order.
+    fn list_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(Slot, String)>, io::Error>;
+
+    /// The events that start on `day`.
+    fn events_starting(&self, day: NaiveDate) -> Result<Vec<Event>, io::Error>;
+
+    /// The events that start on the days from `from` to `to` inclusive. Backends that can read a
+    /// range at once should override this.
+    fn events_starting_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        let mut events = Vec::new();
+        let mut day = from;
+        while day <= to {
+            events.extend(self.events_starting(day)?);
+            day = day.succ();
+        }
+        Ok(events)
+    }
+
+    fn all_events(&self) -> Result<Vec<Event>, io::Error>;
+
+    fn put_event(&self, event: &Event) -> Result<(), io::Error>;
+
+    fn delete_event(&self, event: &Event) -> Result<(), io::Error>;
+
+    fn all_recurring(&self) -> Result<Vec<Recurring>, io::Error>;
+
+    fn put_recurring(&self, recurring: &Recurring) -> Result<(), io::Error>;
+
+    fn delete_recurring(&self, recurring: &Recurring) -> Result<(), io::Error>;
+
+    /// Takes the first event or recurring entry that was left out of a read since this was last
+    /// called because it isn't valid. Reads skip such entries rather than failing, so that one
+    /// bad entry doesn't hide all the others.
+    fn take_invalid(&self) -> Option<io::Error> {
+        None
+    }
+
+    /// Starts calling `changed` when something is changed from outside, eg. by editing the files
+    /// by hand or from another instance of callus. Changes made through this storage may be
+    /// reported too. Watching stops when the returned watcher is dropped. Backends that can't be
+    /// changed from outside return `None`.
+    fn watch(&self, changed: Box<dyn Fn(Change) + Send>) -> Result<Option<RecommendedWatcher>, io::Error> {
+        let _ = changed;
+        Ok(None)
+    }
+}
+
+/// The first entry a backend left out of a read because it isn't valid, waiting for
+/// `Storage::take_invalid`.
+#[derive(Default)]
+pub struct Invalid(Mutex<Option<io::Error>>);
+
+impl Invalid {
+    /// Notes that the entry described by `what` was left out because of `reason`, unless an
+    /// earlier one is still waiting.
+    pub fn add(&self, what: &str, reason: &str) {
+        let mut first = self.0.lock().unwrap();
+        if first.is_none() {
+            *first = Some(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", what, reason)));
+        }
+    }
+
+    pub fn take(&self) -> Option<io::Error> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Copies every entry, event and recurring entry in `from` into `to`, replacing anything already
+/// in `to` for the same slot, event or recurring entry. Returns how many of each were copied.
+pub fn copy(from: &dyn Storage, to: &dyn Storage) -> Result<(usize, usize, usize), io::Error> {
+    let entries = from.list_range(date::MIN, date::MAX)?;
+    for (slot, text) in &entries {
+        to.put(*slot, text)?;
+    }
+    let events = from.all_events()?;
+    for event in &events {
+        to.put_event(event)?;
+    }
+    let recurring = from.all_recurring()?;
+    for r in &recurring {
+        to.put_recurring(r)?;
+    }
+    Ok((entries.len(), events.len(), recurring.len()))
+}
+
+/// Watches `path` and calls `changed` with whatever `to_change` makes of each path that was
+/// modified, created or removed.
+fn watch_path<F>(path: &Path, mode: RecursiveMode, to_change: F, changed: Box<dyn Fn(Change) + Send>)
+    -> Result<RecommendedWatcher, io::Error>
+    where F: Fn(&Path) -> Option<Change> + Send + 'static
+{
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(..) => return,
+        };
+        if let EventKind::Access(..) = event.kind {
+            return;
+        }
+        let mut changes: Vec<Change> = Vec::new();
+        for path in &event.paths {
+            if let Some(change) = to_change(path) {
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+            }
+        }
+        for change in changes {
+            changed(change);
+        }
+    }).map_err(to_io_error)?;
+    watcher.watch(path, mode).map_err(to_io_error)?;
+    Ok(watcher)
+}
+
+/// Watches a storage backend that keeps everything in the file at `path`, reporting any change
+/// to it as `Change::All`. The directory holding it is watched rather than the file itself so
+/// that files replaced by renaming, and files next to it such as SQLite's journal, are noticed.
+fn watch_file(path: &Path, changed: Box<dyn Fn(Change) + Send>) -> Result<RecommendedWatcher, io::Error> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let to_change = move |changed: &Path| {
+        let changed_name = changed.file_name()?.to_string_lossy();
+        match changed_name.starts_with(&name[..]) && !changed_name.ends_with(".tmp") {
+            true => Some(Change::All),
+            false => None,
+        }
+    };
+    watch_path(&dir, RecursiveMode::NonRecursive, to_change, changed)
+}
+
+fn to_io_error(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        _ => io::Error::other(e),
+    }
+}
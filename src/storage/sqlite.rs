@@ -0,0 +1,330 @@
+use chrono::naive::date::NaiveDate;
+use chrono::Datelike;
+use notify::RecommendedWatcher;
+use rusqlite::{self, Connection, OptionalExtension};
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ::database::Slot;
+use ::event::Event;
+use ::recurrence::Recurring;
+use super::{Change, Invalid, Storage};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        day INTEGER NOT NULL,
+        hour INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        PRIMARY KEY (day, hour)
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        day INTEGER NOT NULL,
+        uid TEXT NOT NULL,
+        text TEXT NOT NULL,
+        PRIMARY KEY (day, uid)
+    );
+    CREATE TABLE IF NOT EXISTS recurring (
+        id TEXT NOT NULL PRIMARY KEY,
+        text TEXT NOT NULL
+    );
+";
+
+/// The hour stored for a day's note, which sorts before the day's hours.
+const DAY_HOUR: i64 = -1;
+
+/// Keeps everything in a single SQLite database file. Days are stored as their number of days
+/// since the common era so that a month or week can be read with one range query.
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+    invalid: Invalid,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<SqliteStorage, io::Error> {
+        let conn = Connection::open(path).map_err(to_io_error)?;
+        conn.execute_batch(SCHEMA).map_err(to_io_error)?;
+        Ok(SqliteStorage {
+            path: path.to_owned(),
+            conn: Mutex::new(conn),
+            invalid: Invalid::default(),
+        })
+    }
+
+    /// Runs a query returning `(uid, text)` rows and parses them as events, skipping any that
+    /// aren't valid and keeping the first of those for `take_invalid`.
+    fn load_events<P: rusqlite::Params>(&self, sql: &str, params: P) -> Result<Vec<Event>, io::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(sql).map_err(to_io_error)?;
+        let rows = stmt.query_map(params, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(to_io_error)?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (uid, text) = row.map_err(to_io_error)?;
+            match Event::parse(&uid, &text) {
+                Ok(event) => events.push(event),
+                Err(e) => self.invalid.add(&format!("{}: event `{}`", self.path.display(), uid), &e),
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, slot: Slot) -> Result<String, io::Error> {
+        let (day, hour) = slot_key(slot);
+        let conn = self.conn.lock().unwrap();
+        let text = conn.query_row("SELECT text FROM entries WHERE day = ?1 AND hour = ?2",
+                                  (day, hour), |row| row.get(0))
+            .optional()
+            .map_err(to_io_error)?;
+        Ok(text.unwrap_or_default())
+    }
+
+    fn put(&self, slot: Slot, text: &str) -> Result<(), io::Error> {
+        let (day, hour) = slot_key(slot);
+        let conn = self.conn.lock().unwrap();
+        conn.execute("INSERT OR REPLACE INTO entries (day, hour, text) VALUES (?1, ?2, ?3)", (day, hour, text))
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn delete(&self, slot: Slot) -> Result<(), io::Error> {
+        let (day, hour) = slot_key(slot);
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM entries WHERE day = ?1 AND hour = ?2", (day, hour)).map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn list_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(Slot, String)>, io::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT day, hour, text FROM entries WHERE day BETWEEN ?1 AND ?2 AND text != ''")
+            .map_err(to_io_error)?;
+        let rows = stmt.query_map((day_key(from), day_key(to)), |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?))
+        }).map_err(to_io_error)?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (day, hour, text) = row.map_err(to_io_error)?;
+            let day = match NaiveDate::from_num_days_from_ce_opt(day) {
+                Some(day) => day,
+                None => continue,
+            };
+            let slot = match hour {
+                DAY_HOUR => Slot::Day(day),
+                hour if (0..24).contains(&hour) => Slot::Hour(day, hour as u8),
+                _ => continue,
+            };
+            entries.push((slot, text));
+        }
+        Ok(entries)
+    }
+
+    fn events_starting(&self, day: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        self.events_starting_range(day, day)
+    }
+
+    fn events_starting_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Event>, io::Error> {
+        self.load_events("SELECT uid, text FROM events WHERE day BETWEEN ?1 AND ?2", (day_key(from), day_key(to)))
+    }
+
+    fn all_events(&self) -> Result<Vec<Event>, io::Error> {
+        self.load_events("SELECT uid, text FROM events", ())
+    }
+
+    fn put_event(&self, event: &Event) -> Result<(), io::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("INSERT OR REPLACE INTO events (day, uid, text) VALUES (?1, ?2, ?3)",
+                     (day_key(event.start.date()), &event.uid, event.to_text()))
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn delete_event(&self, event: &Event) -> Result<(), io::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM events WHERE day = ?1 AND uid = ?2", (day_key(event.start.date()), &event.uid))
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn all_recurring(&self) -> Result<Vec<Recurring>, io::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, text FROM recurring").map_err(to_io_error)?;
+        let rows = stmt.query_map((), |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(to_io_error)?;
+        let mut recurring = Vec::new();
+        for row in rows {
+            let (id, text) = row.map_err(to_io_error)?;
+            match Recurring::parse(&id, &text) {
+                Ok(r) => recurring.push(r),
+                Err(e) => self.invalid.add(&format!("{}: recurring entry `{}`", self.path.display(), id), &e),
+            }
+        }
+        Ok(recurring)
+    }
+
+    fn put_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("INSERT OR REPLACE INTO recurring (id, text) VALUES (?1, ?2)", (&recurring.id, recurring.to_text()))
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn delete_recurring(&self, recurring: &Recurring) -> Result<(), io::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM recurring WHERE id = ?1", (&recurring.id,)).map_err(to_io_error)?;
+        Ok(())
+    }
+
+    fn take_invalid(&self) -> Option<io::Error> {
+        self.invalid.take()
+    }
+
+    fn watch(&self, changed: Box<dyn Fn(Change) + Send>) -> Result<Option<RecommendedWatcher>, io::Error> {
+        super::watch_file(&self.path, changed).map(Some)
+    }
+}
+
+fn day_key(day: NaiveDate) -> i32 {
+    day.num_days_from_ce()
+}
+
+fn slot_key(slot: Slot) -> (i32, i64) {
+    match slot {
+        Slot::Day(day) => (day_key(day), DAY_HOUR),
+        Slot::Hour(day, hour) => (day_key(day), hour as i64),
+    }
+}
+
+fn to_io_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::date::NaiveDate;
+
+    use ::TempDir;
+    use ::database::{Database, Slot};
+    use ::editor::Editor;
+    use ::event::Event;
+    use ::recurrence::Recurring;
+    use ::storage::{DirStorage, MemoryStorage, Storage};
+    use super::{day_key, SqliteStorage};
+
+    #[test]
+    fn entries() {
+        let dir = TempDir::new("sqlite-entries");
+        let storage = SqliteStorage::open(&dir.path().join("callus.db")).unwrap();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        storage.put(Slot::Day(day), "Note").unwrap();
+        storage.put(Slot::Hour(day, 0), "Midnight").unwrap();
+        storage.put(Slot::Hour(day, 23), "Late").unwrap();
+        storage.put(Slot::Hour(day + ::one_day(), 8), "Tomorrow").unwrap();
+        storage.put(Slot::Hour(day, 9), "").unwrap();
+        assert_eq!(storage.get(Slot::Day(day)).unwrap(), "Note");
+        assert_eq!(storage.get(Slot::Hour(day, 0)).unwrap(), "Midnight");
+        assert_eq!(storage.get(Slot::Hour(day, 1)).unwrap(), "");
+
+        let mut entries = storage.list_range(day, day).unwrap();
+        entries.sort_by_key(|&(slot, _)| match slot {
+            Slot::Day(..) => -1,
+            Slot::Hour(_, hour) => hour as i32,
+        });
+        assert_eq!(entries, vec![
+            (Slot::Day(day), String::from("Note")),
+            (Slot::Hour(day, 0), String::from("Midnight")),
+            (Slot::Hour(day, 23), String::from("Late")),
+        ]);
+        assert_eq!(storage.list_range(day, day + ::one_day()).unwrap().len(), 4);
+
+        storage.delete(Slot::Day(day)).unwrap();
+        assert_eq!(storage.get(Slot::Day(day)).unwrap(), "");
+        assert_eq!(storage.list_range(day, day).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unknown_hours_are_skipped() {
+        let dir = TempDir::new("sqlite-hours");
+        let storage = SqliteStorage::open(&dir.path().join("callus.db")).unwrap();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        storage.conn.lock().unwrap()
+            .execute("INSERT INTO entries (day, hour, text) VALUES (?1, 24, 'Too late')", (day_key(day),))
+            .unwrap();
+        assert_eq!(storage.list_range(day, day).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn events_and_recurring() {
+        let dir = TempDir::new("sqlite-events");
+        let path = dir.path().join("callus.db");
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        let mut event = Event::new(day, 10);
+        event.title = String::from("Dentist");
+        let mut recurring = Recurring::new(String::from("weekly"), day, Some(15));
+        recurring.text = String::from("Meeting");
+        {
+            let storage = SqliteStorage::open(&path).unwrap();
+            storage.put_event(&event).unwrap();
+            storage.put_recurring(&recurring).unwrap();
+        }
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.events_starting(day).unwrap(), vec![event.clone()]);
+        assert_eq!(storage.events_starting(day + ::one_day()).unwrap(), vec![]);
+        assert_eq!(storage.all_events().unwrap(), vec![event.clone()]);
+        assert_eq!(storage.all_recurring().unwrap(), vec![recurring.clone()]);
+        storage.delete_event(&event).unwrap();
+        storage.delete_recurring(&recurring).unwrap();
+        assert_eq!(storage.all_events().unwrap(), vec![]);
+        assert_eq!(storage.all_recurring().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reports_invalid_rows() {
+        let dir = TempDir::new("sqlite-invalid");
+        let storage = SqliteStorage::open(&dir.path().join("callus.db")).unwrap();
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        {
+            let conn = storage.conn.lock().unwrap();
+            conn.execute("INSERT INTO events (day, uid, text) VALUES (?1, 'broken', 'No fields here')",
+                         (day_key(day),))
+                .unwrap();
+            conn.execute("INSERT INTO recurring (id, text) VALUES ('weekly', 'rrule: FREQ=SOMETIMES')", ()).unwrap();
+        }
+        assert_eq!(storage.events_starting(day).unwrap(), vec![]);
+        assert!(storage.take_invalid().unwrap().to_string().contains("event `broken`"));
+        assert_eq!(storage.all_recurring().unwrap(), vec![]);
+        assert!(storage.take_invalid().unwrap().to_string().contains("recurring entry `weekly`"));
+        assert!(storage.take_invalid().is_none());
+    }
+
+    #[test]
+    fn import() {
+        let dir = TempDir::new("sqlite-import");
+        let from = DirStorage::new(dir.path().join("entries"));
+        let day = NaiveDate::from_ymd(2017, 5, 31);
+        from.put(Slot::Day(day), "Note\n").unwrap();
+        from.put(Slot::Hour(day, 15), "Meeting\n").unwrap();
+        let mut event = Event::new(day, 10);
+        event.title = String::from("Dentist");
+        from.put_event(&event).unwrap();
+        let recurring = Recurring::new(String::from("weekly"), day, None);
+        from.put_recurring(&recurring).unwrap();
+
+        let db = Database::new(Box::new(SqliteStorage::open(&dir.path().join("callus.db")).unwrap()),
+                               Editor::new(None));
+        assert_eq!(db.import_from(&from).unwrap(), (2, 1, 1));
+        assert_eq!(db.get_day(day), "Note\n");
+        assert_eq!(db.get_hour(day, 15), "Meeting\n");
+        assert_eq!(db.get_events(day), vec![event.clone()]);
+
+        let to = MemoryStorage::new();
+        let from = SqliteStorage::open(&dir.path().join("callus.db")).unwrap();
+        assert_eq!(::storage::copy(&from, &to).unwrap(), (2, 1, 1));
+        assert_eq!(to.get(Slot::Hour(day, 15)).unwrap(), "Meeting\n");
+        assert_eq!(to.all_recurring().unwrap(), vec![recurring]);
+    }
+}
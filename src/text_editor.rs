@@ -0,0 +1,183 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::{border, Widget, Block};
+use tui::style::{Modifier, Style};
+
+use termion::event::Key;
+
+use std::cell::Cell;
+use std::cmp::{min, max};
+
+use ::theme::Theme;
+
+pub enum EditResult {
+    Continue,
+    Save(String),
+    Cancel,
+}
+
+/// A multi-line text editor drawn as a popup in the middle of the screen. Long lines are wrapped
+/// to the width of the popup.
+pub struct TextEditor {
+    title: String,
+    lines: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    scroll: Cell<usize>,
+}
+
+impl TextEditor {
+    pub fn new(title: String, text: &str) -> TextEditor {
+        let mut lines = text.lines().map(|line| line.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        let row = lines.len() - 1;
+        let col = lines[row].len();
+        TextEditor {
+            title,
+            lines,
+            row,
+            col,
+            scroll: Cell::new(0),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.lines {
+            text.extend(line.iter());
+            text.push('\n');
+        }
+        while text.ends_with("\n\n") || text == "\n" {
+            text.pop();
+        }
+        text
+    }
+
+    pub fn key(&mut self, key: Key) -> EditResult {
+        match key {
+            Key::Esc => return EditResult::Cancel,
+            Key::Ctrl('s') => return EditResult::Save(self.text()),
+            Key::Left => {
+                if self.col > 0 {
+                    self.col -= 1;
+                } else if self.row > 0 {
+                    self.row -= 1;
+                    self.col = self.lines[self.row].len();
+                }
+            },
+            Key::Right => {
+                if self.col < self.lines[self.row].len() {
+                    self.col += 1;
+                } else if self.row + 1 < self.lines.len() {
+                    self.row += 1;
+                    self.col = 0;
+                }
+            },
+            Key::Up if self.row > 0 => {
+                self.row -= 1;
+                self.col = min(self.col, self.lines[self.row].len());
+            },
+            Key::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = min(self.col, self.lines[self.row].len());
+            },
+            Key::Home | Key::Ctrl('a') => self.col = 0,
+            Key::End | Key::Ctrl('e') => self.col = self.lines[self.row].len(),
+            Key::Backspace => {
+                if self.col > 0 {
+                    self.col -= 1;
+                    self.lines[self.row].remove(self.col);
+                } else if self.row > 0 {
+                    let line = self.lines.remove(self.row);
+                    self.row -= 1;
+                    self.col = self.lines[self.row].len();
+                    self.lines[self.row].extend(line);
+                }
+            },
+            Key::Delete => {
+                if self.col < self.lines[self.row].len() {
+                    self.lines[self.row].remove(self.col);
+                } else if self.row + 1 < self.lines.len() {
+                    let line = self.lines.remove(self.row + 1);
+                    self.lines[self.row].extend(line);
+                }
+            },
+            Key::Char('\n') => {
+                let rest = self.lines[self.row].split_off(self.col);
+                self.row += 1;
+                self.col = 0;
+                self.lines.insert(self.row, rest);
+            },
+            Key::Char(c) => {
+                self.lines[self.row].insert(self.col, c);
+                self.col += 1;
+            },
+            _ => (),
+        }
+        EditResult::Continue
+    }
+
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer, theme: &Theme) {
+        let width = min(area.width, max(20, area.width * 2 / 3));
+        let height = min(area.height, max(5, area.height / 2));
+        let rect = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        if rect.width < 3 || rect.height < 3 {
+            return;
+        }
+
+        for y in rect.top()..rect.bottom() {
+            for x in rect.left()..rect.right() {
+                buffer.get_mut(x, y).reset();
+            }
+        }
+        let title = format!("{} (^S save, Esc cancel)", self.title);
+        let block = Block::default()
+                .title(&title)
+                .borders(border::ALL)
+                .title_style(theme.title.style());
+        block.draw(&rect, buffer);
+        let inner = block.inner(&rect);
+
+        // Each line is wrapped into one or more screen rows of `inner.width` characters.
+        let text_width = inner.width as usize;
+        let mut rows = Vec::new();
+        let mut cursor = (0, 0);
+        for (row, line) in self.lines.iter().enumerate() {
+            let chunks = max(1, (line.len() + text_width) / text_width);
+            for chunk in 0..chunks {
+                let start = chunk * text_width;
+                let end = min(line.len(), start + text_width);
+                if row == self.row && self.col >= start && self.col < start + text_width {
+                    cursor = (rows.len(), self.col - start);
+                }
+                rows.push(&line[min(start, end)..end]);
+            }
+        }
+
+        let visible = inner.height as usize;
+        if cursor.0 < self.scroll.get() {
+            self.scroll.set(cursor.0);
+        }
+        if cursor.0 >= self.scroll.get() + visible {
+            self.scroll.set(cursor.0 + 1 - visible);
+        }
+
+        let scroll = self.scroll.get();
+        for (i, row) in rows.iter().enumerate().skip(scroll).take(visible) {
+            let y = inner.y + (i - scroll) as u16;
+            let s = row.iter().collect::<String>();
+            buffer.set_stringn(inner.x, y, &s, text_width, &Style::default());
+        }
+        let cursor_x = inner.x + cursor.1 as u16;
+        let cursor_y = inner.y + (cursor.0 - scroll) as u16;
+        buffer.get_mut(cursor_x, cursor_y).set_modifier(Modifier::Invert);
+    }
+}
+
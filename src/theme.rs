@@ -0,0 +1,313 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+
+use std::env;
+
+use ::config::ThemeConfig;
+use ::error::Error;
+
+/// Part of a style: whatever isn't set is left alone when it's applied on top of another style.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ThemeStyle {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    modifier: Option<Modifier>,
+}
+
+impl ThemeStyle {
+    /// `style` with this on top. A cell can only have one modifier, so reverse video is kept over
+    /// any other since the monochrome theme uses it to show what's selected.
+    pub fn apply(self, mut style: Style) -> Style {
+        if let Some(fg) = self.fg {
+            style.fg = fg;
+        }
+        if let Some(bg) = self.bg {
+            style.bg = bg;
+        }
+        match self.modifier {
+            Some(_) if style.modifier == Modifier::Invert => (),
+            Some(modifier) => style.modifier = modifier,
+            None => (),
+        }
+        style
+    }
+
+    pub fn style(self) -> Style {
+        self.apply(Style::default())
+    }
+
+    /// Reads a style written like `black on red`, `yellow bold` or `reverse`: an optional
+    /// foreground color, `on` and a background color, and one of `bold`, `underline`, `reverse`,
+    /// `italic` or `dim`. An empty string or `none` leaves everything as it is.
+    pub fn parse(s: &str) -> Option<ThemeStyle> {
+        let mut style = ThemeStyle::default();
+        let mut words = s.split_whitespace().map(str::to_lowercase);
+        while let Some(word) = words.next() {
+            if word == "on" {
+                style.bg = Some(parse_color(&words.next()?)?);
+            } else if let Some(modifier) = parse_modifier(&word) {
+                if style.modifier.is_some() {
+                    return None;
+                }
+                style.modifier = Some(modifier);
+            } else if word != "none" && style.fg.is_none() {
+                style.fg = Some(parse_color(&word)?);
+            } else if word != "none" {
+                return None;
+            }
+        }
+        Some(style)
+    }
+}
+
+/// Bold text on top of `style`, or reverse video if that's what it has.
+pub fn bold(style: Style) -> Style {
+    let bold = ThemeStyle {
+        modifier: Some(Modifier::Bold),
+        ..ThemeStyle::default()
+    };
+    bold.apply(style)
+}
+
+/// Gives every cell in `rect` a style, such as the selected style inside a selected box. Unlike a
+/// block's style, which only sets the background, this covers reverse video too.
+pub fn fill(rect: &Rect, buffer: &mut Buffer, style: &Style) {
+    for y in rect.top()..rect.bottom() {
+        for x in rect.left()..rect.right() {
+            buffer.get_mut(x, y).set_style(*style);
+        }
+    }
+}
+
+/// The styles the calendar is drawn with. Where several apply, eg. to today's date when it's
+/// selected, they're applied in the order they're listed here.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    /// The text of entries.
+    pub entry: ThemeStyle,
+    /// Events in the week view.
+    pub event: ThemeStyle,
+    /// Days with entries in the year view.
+    pub has_entries: ThemeStyle,
+    /// Days outside the month being shown in the month view.
+    pub other_month: ThemeStyle,
+    /// Saturdays and Sundays.
+    pub weekend: ThemeStyle,
+    pub selected: ThemeStyle,
+    /// Today, and the current hour.
+    pub today: ThemeStyle,
+    /// The names of the days above each column.
+    pub header: ThemeStyle,
+    /// The title of each view and popup.
+    pub title: ThemeStyle,
+    /// The bottom line.
+    pub status: ThemeStyle,
+    /// Messages on the bottom line.
+    pub message: ThemeStyle,
+}
+
+/// The built-in themes as style names and styles, which a theme's settings replace.
+const DEFAULT: [(&str, &str); 11] = [
+    ("entry", ""),
+    ("event", "black on cyan"),
+    ("has_entries", "cyan underline"),
+    ("other_month", "red"),
+    ("weekend", ""),
+    ("selected", "black on red"),
+    ("today", "yellow"),
+    ("header", "bold"),
+    ("title", "yellow bold"),
+    ("status", "black on white"),
+    ("message", "red bold"),
+];
+
+/// For terminals with a light background, and without red and green.
+const LIGHT: [(&str, &str); 11] = [
+    ("entry", ""),
+    ("event", "black on lightcyan"),
+    ("has_entries", "magenta underline"),
+    ("other_month", "darkgray"),
+    ("weekend", "darkgray"),
+    ("selected", "white on black"),
+    ("today", "magenta bold"),
+    ("header", "bold"),
+    ("title", "magenta bold"),
+    ("status", "white on black"),
+    ("message", "lightyellow bold"),
+];
+
+/// No colors at all, with the selection in reverse video.
+const MONOCHROME: [(&str, &str); 11] = [
+    ("entry", ""),
+    ("event", "underline"),
+    ("has_entries", "bold"),
+    ("other_month", ""),
+    ("weekend", ""),
+    ("selected", "reverse"),
+    ("today", "underline"),
+    ("header", "bold"),
+    ("title", "bold"),
+    ("status", "reverse"),
+    ("message", "bold"),
+];
+
+impl Theme {
+    /// Builds the theme named in the `[theme]` section with its styles replaced by any set there.
+    /// Without a name, the default theme is used, or the monochrome one if the terminal looks like
+    /// it has no colors.
+    pub fn new(config: &ThemeConfig) -> Result<Theme, Error> {
+        let name = match config.name {
+            Some(ref name) => &name[..],
+            None if colors_unavailable() => "monochrome",
+            None => "default",
+        };
+        let built_in = match name {
+            "default" => DEFAULT,
+            "light" => LIGHT,
+            "monochrome" => MONOCHROME,
+            _ => {
+                return Err(Error::Config(format!("unknown theme `{}`, try `default`, `light` or `monochrome`",
+                                                 name)));
+            },
+        };
+
+        let mut theme = Theme {
+            entry: ThemeStyle::default(),
+            event: ThemeStyle::default(),
+            has_entries: ThemeStyle::default(),
+            other_month: ThemeStyle::default(),
+            weekend: ThemeStyle::default(),
+            selected: ThemeStyle::default(),
+            today: ThemeStyle::default(),
+            header: ThemeStyle::default(),
+            title: ThemeStyle::default(),
+            status: ThemeStyle::default(),
+            message: ThemeStyle::default(),
+        };
+        let settings = built_in.iter().cloned()
+            .chain(config.styles.iter().map(|(name, style)| (&name[..], &style[..])));
+        for (name, spec) in settings {
+            let style = ThemeStyle::parse(spec)
+                .ok_or_else(|| Error::Config(format!("invalid style `{}` for `{}`", spec, name)))?;
+            *match name {
+                "entry" => &mut theme.entry,
+                "event" => &mut theme.event,
+                "has_entries" => &mut theme.has_entries,
+                "other_month" => &mut theme.other_month,
+                "weekend" => &mut theme.weekend,
+                "selected" => &mut theme.selected,
+                "today" => &mut theme.today,
+                "header" => &mut theme.header,
+                "title" => &mut theme.title,
+                "status" => &mut theme.status,
+                "message" => &mut theme.message,
+                _ => return Err(Error::Config(format!("unknown style `{}` in [theme]", name))),
+            } = style;
+        }
+        Ok(theme)
+    }
+}
+
+/// Whether colors should be left out, going by the `NO_COLOR` convention and `$TERM`.
+fn colors_unavailable() -> bool {
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return true;
+    }
+    match env::var("TERM") {
+        Ok(term) => term.is_empty() || term == "dumb",
+        Err(_) => true,
+    }
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    Some(match name {
+        "default" | "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    Some(match name {
+        "bold" => Modifier::Bold,
+        "underline" => Modifier::Underline,
+        "reverse" => Modifier::Invert,
+        "italic" => Modifier::Italic,
+        "dim" => Modifier::Faint,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::style::{Color, Modifier, Style};
+
+    use ::config::ThemeConfig;
+    use super::{Theme, ThemeStyle};
+
+    fn config(name: &str, styles: &[(&str, &str)]) -> ThemeConfig {
+        ThemeConfig {
+            name: Some(String::from(name)),
+            styles: styles.iter().map(|&(name, style)| (String::from(name), String::from(style))).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_styles() {
+        let style = |fg, bg, modifier| Some(ThemeStyle { fg, bg, modifier });
+        assert_eq!(ThemeStyle::parse("black on red"), style(Some(Color::Black), Some(Color::Red), None));
+        assert_eq!(ThemeStyle::parse("Yellow BOLD"), style(Some(Color::Yellow), None, Some(Modifier::Bold)));
+        assert_eq!(ThemeStyle::parse("on #0080ff reverse"),
+                   style(None, Some(Color::Rgb(0, 0x80, 0xff)), Some(Modifier::Invert)));
+        assert_eq!(ThemeStyle::parse(""), Some(ThemeStyle::default()));
+        assert_eq!(ThemeStyle::parse("none"), Some(ThemeStyle::default()));
+        for bad in &["purple", "red green", "bold italic", "red on", "on nothing", "#12345", "#12345g"] {
+            assert_eq!(ThemeStyle::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn applies_over_other_styles() {
+        let selected = ThemeStyle::parse("black on red").unwrap().style();
+        let today = ThemeStyle::parse("yellow bold").unwrap().apply(selected);
+        assert_eq!(today, Style::default().fg(Color::Yellow).bg(Color::Red).modifier(Modifier::Bold));
+        let reversed = ThemeStyle::parse("reverse").unwrap().style();
+        assert_eq!(ThemeStyle::parse("bold").unwrap().apply(reversed).modifier, Modifier::Invert);
+    }
+
+    #[test]
+    fn themes() {
+        let theme = Theme::new(&config("default", &[])).unwrap();
+        assert_eq!(theme.selected, ThemeStyle::parse("black on red").unwrap());
+        let theme = Theme::new(&config("monochrome", &[("today", "bold"), ("event", "none")])).unwrap();
+        assert_eq!(theme.selected, ThemeStyle::parse("reverse").unwrap());
+        assert_eq!(theme.today, ThemeStyle::parse("bold").unwrap());
+        assert_eq!(theme.event, ThemeStyle::default());
+        assert!(Theme::new(&config("light", &[])).is_ok());
+
+        assert!(Theme::new(&config("solarized", &[])).is_err());
+        assert!(Theme::new(&config("default", &[("sidebar", "red")])).is_err());
+        assert!(Theme::new(&config("default", &[("today", "very yellow")])).is_err());
+    }
+}
@@ -1,19 +1,25 @@
 use tui::buffer::Buffer;
 use tui::layout::Rect;
 use tui::widgets::{border, Widget, Block};
-use tui::style::{Color, Modifier, Style};
+use tui::style::Style;
 
 use chrono::naive::date::NaiveDate;
+use chrono::naive::datetime::NaiveDateTime;
 use chrono::offset::local::Local;
-use chrono::{Datelike, Timelike};
+use chrono::{Datelike, Duration, Timelike};
 
 use std::cmp::{min, max};
+use std::collections::HashSet;
+use std::mem;
 use std::sync::Arc;
 use std::cell::RefCell;
 
-use ::day_suffix;
-use ::{DAY_NAMES, MONTH_NAMES, one_day};
-use ::database::Database;
+use ::hour_name;
+use ::{HitAreas, one_day};
+use ::locale;
+use ::database::{Database, Slot};
+use ::event::Event;
+use ::theme::{self, Theme};
 
 pub struct WeekView {
     selected_date: NaiveDate,
@@ -21,16 +27,18 @@ pub struct WeekView {
     top_left_date: RefCell<NaiveDate>,
     top_hour: RefCell<u8>,
     db: Arc<Database>,
+    theme: Theme,
+    hit_areas: HitAreas,
 }
 
 impl WeekView {
-    pub fn new(db: Arc<Database>, date: NaiveDate, hour: u8) -> WeekView {
+    pub fn new(db: Arc<Database>, theme: Theme, date: NaiveDate, hour: u8) -> WeekView {
         let top_date = if hour == 0 {
             date - one_day()
         } else {
             date
         };
-        let days_offset = top_date.weekday().num_days_from_monday();
+        let days_offset = locale::get().day_of_week(top_date.weekday());
         let days_offset = max(days_offset, 1);
         let days_offset = min(days_offset, 5);
 
@@ -39,7 +47,9 @@ impl WeekView {
             selected_hour: hour,
             top_left_date: RefCell::new(date - (one_day() * days_offset as i32)),
             top_hour: RefCell::new((hour + 23) % 24),
-            db: db,
+            db,
+            theme,
+            hit_areas: HitAreas::default(),
         }
     }
 
@@ -51,7 +61,7 @@ impl WeekView {
         } else {
             date
         };
-        let days_offset = top_date.weekday().num_days_from_monday();
+        let days_offset = locale::get().day_of_week(top_date.weekday());
         let days_offset = max(days_offset, 1);
         let days_offset = min(days_offset, 5);
 
@@ -67,6 +77,21 @@ impl WeekView {
         self.selected_date
     }
 
+    pub fn set_hour(&mut self, hour: u8) {
+        self.selected_hour = hour;
+    }
+
+    pub fn get_hour(&self) -> u8 {
+        self.selected_hour
+    }
+
+    /// Selects an hour and leaves the view where it is, unlike `set_date`. If the hour is only
+    /// partly on screen, the next draw scrolls to it as it does after moving with the keys.
+    pub fn select(&mut self, date: NaiveDate, hour: u8) {
+        self.selected_date = date;
+        self.selected_hour = hour;
+    }
+
     pub fn up(&mut self) -> bool {
         if self.selected_hour == 0 {
             self.selected_hour = 23;
@@ -97,14 +122,17 @@ impl WeekView {
         false
     }
 
-    pub fn enter(&mut self) -> bool {
-        let date = self.selected_date;
-        let hour = self.selected_hour;
-        self.db.edit_hour(date, hour);
-        true
+    pub fn selected_slot(&self) -> Slot {
+        Slot::Hour(self.selected_date, self.selected_hour)
+    }
+
+    /// The hour drawn at `x`, `y` as of the last draw.
+    pub fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        self.hit_areas.slot_at(x, y)
     }
 
     pub fn draw(&self, area: &Rect, buffer: &mut Buffer) {
+        self.hit_areas.clear();
         //let title = format!("{} {}", MONTH_NAMES[self.selected_date.month0() as usize], self.selected_date.year());
         //buffer.set_string((area.width - title.len() as u16) / 2, 0, &title, &Style::default().fg(Color::Yellow).modifier(Modifier::Bold));
         
@@ -137,6 +165,12 @@ impl WeekView {
         let mut move_up = false;
         let mut move_down = false;
         //let mut debug = Vec::new();
+        {
+            // Scrolling can move the view a day either way, and tall rows can spill into the
+            // next week.
+            let top_left_date = *self.top_left_date.borrow();
+            self.db.prefetch(top_left_date - one_day(), top_left_date + one_day() * 14);
+        }
         'redraw: loop {
             if redraw_count == 30 {
                 break;
@@ -180,8 +214,8 @@ impl WeekView {
             loop {
                 let mut new_row = Row {
                     show_date: false,
-                    hour: hour,
-                    y: y,
+                    hour,
+                    y,
                     box_height: 3,
                     entries: Vec::new(),
                 };
@@ -202,7 +236,7 @@ impl WeekView {
                     new_row.box_height = max(new_row.box_height, summary.lines().count() as u16);
                     let new_entry = Entry {
                         summary: summary.to_owned(),
-                        selected: selected,
+                        selected,
                         today: is_today,
                         date: day,
                     };
@@ -232,7 +266,7 @@ impl WeekView {
                 if y > area.height {
                     break;
                 }
-                hour = hour + 1;
+                hour += 1;
                 if hour == 24 {
                     hour = 0;
                     left_date = left_date + one_day();
@@ -242,81 +276,242 @@ impl WeekView {
             break;
         }
 
+        let column = |day_offset: usize| {
+            let this_x = (area.width + 1) * day_offset as u16 / 7;
+            let next_x = (area.width + 1) * (day_offset as u16 + 1) / 7;
+            (this_x, next_x - this_x - if day_offset == 6 { 1 } else { 0 })
+        };
+
+        // Events are drawn as blocks over the hour boxes, from the line their start falls on to
+        // the line their end falls on, side by side where they overlap. Where they share hours with
+        // entries they take the right half of the column and the entries the left.
+        let mut blocks = Vec::new();
+        let mut narrowed = HashSet::new();
+        for day_offset in 0..7 {
+            let (x, width) = column(day_offset);
+            let text_width = width.saturating_sub(2);
+            // A column goes on to the next day at midnight, so it's laid out a day at a time.
+            let mut dates = rows.iter().map(|row| row.entries[day_offset].date).collect::<Vec<_>>();
+            dates.dedup();
+            for date in dates {
+                let day_rows = rows.iter().filter(|row| row.entries[day_offset].date == date).collect::<Vec<_>>();
+                // The first and last line of each hour's box, not counting its top border.
+                let lines = day_rows.iter().map(|row| {
+                    let top = row.y + if row.show_date { 2 } else { 1 };
+                    (row.hour, top, max(top, row.y + if row.show_date { 1 } else { 0 } + row.box_height))
+                }).collect::<Vec<_>>();
+                let line_at = |time: NaiveDateTime, is_end: bool| {
+                    // The end of an hour is the bottom of its box rather than the top of the next.
+                    let hour_time = if is_end { time - Duration::minutes(1) } else { time };
+                    let hour = if hour_time.date() < date { 0 } else { hour_time.hour() as u8 };
+                    let minutes = time.signed_duration_since(date.and_hms(hour as u32, 0, 0)).num_minutes().clamp(0, 60) as u16;
+                    match lines.iter().find(|&&(line_hour, ..)| line_hour == hour) {
+                        Some(&(_, top, bottom)) if is_end => top + ((bottom - top) * minutes).div_ceil(60),
+                        Some(&(_, top, bottom)) => top + (bottom - top) * minutes / 60,
+                        None if hour < lines[0].0 => lines[0].1,
+                        None => lines[lines.len() - 1].2,
+                    }
+                };
+
+                let shown_from = date.and_hms(lines[0].0 as u32, 0, 0);
+                let shown_to = date.and_hms(lines[lines.len() - 1].0 as u32, 0, 0) + Duration::hours(1);
+                let events = self.db.get_events(date)
+                    .into_iter()
+                    .filter(|event| !event.all_day && event.start < shown_to)
+                    .filter(|event| event.end > shown_from || event.start == shown_from)
+                    .collect();
+                for (group, lanes) in lay_out(events) {
+                    let group_start = group.iter().map(|(event, _)| event.start).min().unwrap();
+                    let group_end = group.iter().map(|(event, _)| event.end).max().unwrap();
+                    let overlapped_hours = day_rows.iter()
+                        .filter(|row| {
+                            let hour_start = date.and_hms(row.hour as u32, 0, 0);
+                            group_start < hour_start + Duration::hours(1) && group_end > hour_start
+                        })
+                        .map(|row| (row.hour, !row.entries[day_offset].summary.is_empty()))
+                        .collect::<Vec<_>>();
+                    let beside_entries = overlapped_hours.iter().any(|&(_, has_text)| has_text);
+                    let (events_x, events_width) = match beside_entries {
+                        true => {
+                            narrowed.extend(overlapped_hours.iter().map(|&(hour, _)| (date, hour)));
+                            (x + 1 + text_width / 2, text_width - text_width / 2)
+                        },
+                        false => (x + 1, text_width),
+                    };
+
+                    for (event, lane) in group {
+                        let start = max(event.start, date.and_hms(0, 0, 0));
+                        let end = min(event.end, date.succ().and_hms(0, 0, 0));
+                        let top = line_at(start, false);
+                        let bottom = min(max(line_at(end, true), top + 1), area.height);
+                        let lane_x = events_x + events_width * lane as u16 / lanes as u16;
+                        let next_lane_x = events_x + events_width * (lane as u16 + 1) / lanes as u16;
+                        // Leave a gap between lanes, if there's room.
+                        let lane_width = match lane + 1 < lanes && next_lane_x - lane_x > 1 {
+                            true => next_lane_x - lane_x - 1,
+                            false => next_lane_x - lane_x,
+                        };
+                        if top >= bottom || lane_width == 0 {
+                            continue;
+                        }
+                        let rect = Rect {
+                            x: lane_x,
+                            y: top,
+                            width: lane_width,
+                            height: bottom - top,
+                        };
+                        blocks.push((rect, event));
+                    }
+                }
+            }
+        }
+
         //println!("#rpws == {}", rows.len());
-        for row in rows {
-            for (day_offset, entry) in row.entries.into_iter().enumerate() {
+        for row in &rows {
+            for (day_offset, entry) in row.entries.iter().enumerate() {
                 if row.show_date {
-                    let day_of_month = entry.date.day();
-                    let day_of_week = entry.date.weekday().num_days_from_monday();
-                    let month = entry.date.month0();
-                    let year = entry.date.year();
-                    let column_title = format!("{} {}{} {} {}",
-                            DAY_NAMES[day_of_week as usize],
-                            day_of_month,
-                            day_suffix(day_of_month),
-                            MONTH_NAMES[month as usize],
-                            year);
-                    let x = 1 + (1 + day_offset as u16 * 2) * area.width / 14 - column_title.len() as u16 / 2;
-                    buffer.set_string(x, row.y, &column_title, &Style::default().modifier(Modifier::Bold));
+                    let column_title = locale::get().date_title(entry.date);
+                    // Centred over the column, and cut short where the column is too narrow for it.
+                    let (column_x, column_width) = column(day_offset);
+                    let title_width = min(column_title.chars().count() as u16, column_width);
+                    let x = (1 + (1 + day_offset as u16 * 2) * area.width / 14).saturating_sub(title_width / 2);
+                    let x = min(max(x, column_x), column_x + column_width - title_width);
+                    let mut header_style = self.theme.header.style();
+                    if entry.date.weekday().num_days_from_monday() >= 5 {
+                        header_style = self.theme.weekend.apply(header_style);
+                    }
+                    buffer.set_stringn(x, row.y, &column_title, title_width as usize, &header_style);
                 }
 
-                let this_x = (area.width + 1) * day_offset as u16 / 7;
-                let next_x = (area.width + 1) * (day_offset as u16 + 1) / 7;
+                let (x, width) = column(day_offset);
                 let rect = Rect {
-                    x: this_x,
+                    x,
                     y: row.y + if row.show_date { 1 } else { 0 },
-                    width: next_x - this_x - if day_offset == 6 { 1 } else { 0 },
+                    width,
                     height: row.box_height,
                 };
+                self.hit_areas.add(rect, Slot::Hour(entry.date, row.hour));
 
-                let fg = match entry.today {
-                    true => Some(Color::Yellow),
-                    false => match entry.selected {
-                        true => Some(Color::Black),
-                        false => None,
-                    }
-                };
-                let bg = match entry.selected {
-                    true => Some(Color::Red),
-                    false => None,
-                };
                 let number_style = {
                     let mut number_style = Style::default();
-                    if let Some(fg) = fg {
-                        number_style = number_style.fg(fg);
+                    if entry.selected {
+                        number_style = self.theme.selected.apply(number_style);
                     }
-                    if let Some(bg) = bg {
-                        number_style = number_style.bg(bg);
+                    if entry.today {
+                        number_style = self.theme.today.apply(number_style);
                     }
                     number_style
                 };
-                let block_style = {
-                    let mut block_style = Style::default();
-                    if let Some(bg) = bg {
-                        block_style = block_style.bg(bg);
-                    }
-                    block_style
-                };
 
-                let hour_str = format!("{}{}", row.hour, if row.hour < 12 { "AM" } else { "PM" });
+                let hour_str = hour_name(row.hour);
                 let block = Block::default()
                         .title(&hour_str)
                         .borders(border::TOP)
                         .title_style(number_style)
-                        .border_style(number_style)
-                        .style(block_style);
+                        .border_style(number_style);
                 block.draw(&rect, buffer);
 
-                let mut style = Style::default();
+                let mut style = self.theme.entry.style();
                 if entry.selected {
-                    style = style.fg(Color::Black).bg(Color::Red);
+                    theme::fill(&block.inner(&rect), buffer, &self.theme.selected.style());
+                    style = self.theme.selected.apply(style);
                 }
+                let text_width = match narrowed.contains(&(entry.date, row.hour)) {
+                    true => rect.width.saturating_sub(2) as usize / 2,
+                    false => rect.width.saturating_sub(2) as usize,
+                };
                 for (line, sy) in entry.summary.lines().zip((rect.y + 1)..area.height) {
-                    buffer.set_stringn(rect.x + 1, sy, line, (rect.width - 2) as usize, &style);
+                    buffer.set_stringn(rect.x + 1, sy, line, text_width, &style);
                 }
             }
         }
+
+        let event_style = self.theme.event.style();
+        for (rect, event) in blocks {
+            let lines = match rect.height {
+                1 => vec![format!("{} {}", event.time_range(), event.title)],
+                _ => vec![event.title.clone(), event.time_range(), event.location.clone().unwrap_or_default()],
+            };
+            let width = rect.width as usize;
+            for (i, y) in (rect.top()..rect.bottom()).enumerate() {
+                // Padded so that the block covers the borders of the hours it spans.
+                let line = format!("{:1$}", lines.get(i).map(|line| &line[..]).unwrap_or(""), width);
+                buffer.set_stringn(rect.x, y, &line, width, &event_style);
+            }
+        }
     }
 }
 
+/// Splits events sorted by start time into groups that overlap one another, giving each event a
+/// lane to be drawn in so that no two events in a lane overlap. Returns each group with the
+/// number of lanes it needs.
+fn lay_out(events: Vec<Event>) -> Vec<(Vec<(Event, usize)>, usize)> {
+    let mut groups = Vec::new();
+    let mut group = Vec::new();
+    let mut lane_ends: Vec<NaiveDateTime> = Vec::new();
+    for event in events {
+        // Events without a length still take up a line.
+        let end = max(event.end, event.start + Duration::minutes(1));
+        if lane_ends.iter().all(|&lane_end| lane_end <= event.start) && !group.is_empty() {
+            groups.push((mem::take(&mut group), lane_ends.len()));
+            lane_ends.clear();
+        }
+        let lane = match lane_ends.iter().position(|&lane_end| lane_end <= event.start) {
+            Some(lane) => {
+                lane_ends[lane] = end;
+                lane
+            },
+            None => {
+                lane_ends.push(end);
+                lane_ends.len() - 1
+            },
+        };
+        group.push((event, lane));
+    }
+    if !group.is_empty() {
+        groups.push((group, lane_ends.len()));
+    }
+    groups
+}
 
+#[cfg(test)]
+mod tests {
+    use tui::buffer::Buffer;
+    use tui::layout::Rect;
+
+    use chrono::naive::date::NaiveDate;
+
+    use std::sync::Arc;
+
+    use super::WeekView;
+    use ::locale;
+    use ::config::ThemeConfig;
+    use ::database::Database;
+    use ::editor::Editor;
+    use ::storage::memory::MemoryStorage;
+    use ::theme::Theme;
+
+    fn draw(width: u16) -> Buffer {
+        let db = Arc::new(Database::new(Box::new(MemoryStorage::new()), Editor::new(None)));
+        let theme = Theme::new(&ThemeConfig::default()).unwrap();
+        let view = WeekView::new(db, theme, NaiveDate::from_ymd(2017, 5, 31), 9);
+        let area = Rect { x: 0, y: 0, width, height: 30 };
+        let mut buffer = Buffer::empty(area);
+        view.draw(&area, &mut buffer);
+        buffer
+    }
+
+    fn line(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area().width).map(|x| buffer.get(x, y).symbol.clone()).collect()
+    }
+
+    #[test]
+    fn headers() {
+        let title = locale::get().date_title(NaiveDate::from_ymd(2017, 5, 31));
+        assert!((0..30).any(|y| line(&draw(140), y).contains(&title[..])));
+        // Too narrow for the titles, which are cut short rather than overflowing.
+        for width in 14..30 {
+            draw(width);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+
+use chrono::naive::date::NaiveDate;
+use chrono::offset::local::Local;
+use chrono::Datelike;
+
+use std::cmp::max;
+use std::sync::Arc;
+
+use ::{HitAreas, one_day};
+use ::locale;
+use ::database::{Database, Slot};
+use ::theme::Theme;
+
+const MONTH_WIDTH: u16 = 7 * 3;
+const MONTH_HEIGHT: u16 = 8;
+
+pub struct YearView {
+    selected_date: NaiveDate,
+    db: Arc<Database>,
+    theme: Theme,
+    hit_areas: HitAreas,
+}
+
+impl YearView {
+    pub fn new(db: Arc<Database>, theme: Theme, date: NaiveDate) -> YearView {
+        YearView {
+            selected_date: date,
+            db,
+            theme,
+            hit_areas: HitAreas::default(),
+        }
+    }
+
+    pub fn set_date(&mut self, date: NaiveDate) {
+        self.selected_date = date;
+    }
+
+    pub fn get_date(&self) -> NaiveDate {
+        self.selected_date
+    }
+
+    pub fn up(&mut self) -> bool {
+        self.selected_date = self.selected_date - one_day() * 7;
+        false
+    }
+
+    pub fn down(&mut self) -> bool {
+        self.selected_date = self.selected_date + one_day() * 7;
+        false
+    }
+
+    pub fn left(&mut self) -> bool {
+        self.selected_date = self.selected_date - one_day();
+        false
+    }
+
+    pub fn right(&mut self) -> bool {
+        self.selected_date = self.selected_date + one_day();
+        false
+    }
+
+    /// The day drawn at `x`, `y` as of the last draw.
+    pub fn slot_at(&self, x: u16, y: u16) -> Option<Slot> {
+        self.hit_areas.slot_at(x, y)
+    }
+
+    pub fn draw(&self, area: &Rect, buffer: &mut Buffer) {
+        self.hit_areas.clear();
+        if area.width < MONTH_WIDTH || area.height < 2 {
+            return;
+        }
+
+        let year = self.selected_date.year();
+        let title = format!("{}", year);
+        buffer.set_string(area.x + (area.width - title.len() as u16) / 2, area.y, &title, &self.theme.title.style());
+
+        let columns = (area.width / (MONTH_WIDTH + 2)).clamp(1, 4);
+        let rows = 12u16.div_ceil(columns);
+        let cell_width = area.width / columns;
+        let cell_height = max(MONTH_HEIGHT + 1, (area.height - 2) / rows);
+        let today = Local::now().naive_local().date();
+        self.db.prefetch(NaiveDate::from_ymd(year, 1, 1), NaiveDate::from_ymd(year, 12, 31));
+
+        for month0 in 0..12u16 {
+            let x = area.x + (month0 % columns) * cell_width + (cell_width - MONTH_WIDTH) / 2;
+            let y = area.y + 2 + (month0 / columns) * cell_height;
+            let first_day_of_month = NaiveDate::from_ymd(year, month0 as u32 + 1, 1);
+            self.draw_month(first_day_of_month, today, x, y, area, buffer);
+        }
+    }
+
+    fn draw_month(&self, first_day_of_month: NaiveDate, today: NaiveDate, x: u16, y: u16, area: &Rect, buffer: &mut Buffer) {
+        let mut set_string = |x: u16, y: u16, s: &str, style: &Style| {
+            if y < area.bottom() && x < area.right() {
+                buffer.set_stringn(x, y, s, (area.right() - x) as usize, style);
+            }
+        };
+
+        let locale = locale::get();
+        let month_name = locale.month_name(first_day_of_month.month0());
+        let month_style = match first_day_of_month.month0() == self.selected_date.month0() {
+            true => self.theme.title.style(),
+            false => self.theme.header.style(),
+        };
+        let name_width = month_name.chars().count() as u16;
+        set_string(x + MONTH_WIDTH.saturating_sub(name_width) / 2, y, month_name, &month_style);
+
+        for (day_num, weekday) in locale.weekdays().into_iter().enumerate() {
+            let mut header_style = self.theme.header.style();
+            if weekday.num_days_from_monday() >= 5 {
+                header_style = self.theme.weekend.apply(header_style);
+            }
+            let day_name = locale.day_name(weekday).chars().take(2).collect::<String>();
+            set_string(x + day_num as u16 * 3, y + 1, &day_name, &header_style);
+        }
+
+        let mut day = first_day_of_month;
+        while day.month0() == first_day_of_month.month0() {
+            let day_num = locale.day_of_week(day.weekday());
+            let week_num = (locale.day_of_week(first_day_of_month.weekday()) + day.day0()) / 7;
+
+            let style = {
+                let mut style = Style::default();
+                if self.db.has_entries(day) {
+                    style = self.theme.has_entries.apply(style);
+                }
+                if day.weekday().num_days_from_monday() >= 5 {
+                    style = self.theme.weekend.apply(style);
+                }
+                if day == self.selected_date {
+                    style = self.theme.selected.apply(style);
+                }
+                if day == today {
+                    style = self.theme.today.apply(style);
+                }
+                style
+            };
+
+            let day_of_month = format!("{:>2}", day.day());
+            set_string(x + day_num as u16 * 3, y + 2 + week_num as u16, &day_of_month, &style);
+            let rect = Rect {
+                x: x + day_num as u16 * 3,
+                y: y + 2 + week_num as u16,
+                width: 2,
+                height: 1,
+            };
+            self.hit_areas.add(rect, Slot::Day(day));
+            day = day + one_day();
+        }
+    }
+}
+